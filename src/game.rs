@@ -0,0 +1,729 @@
+//! A whole game of Tetris, free of any terminal or timer, driven one command
+//! and one slice of time at a time.
+use crate::game_mode::GameMode;
+use crate::hold::HoldSlot;
+use crate::level_curve::{Gravity, LevelCurve, LevelCurveKind};
+use crate::models::{
+    Command, GameEvent, GameOverReason, GarbageHoles, LineClear, PieceShape, TetrisBoard,
+    TetrisPiece, TurnEvent,
+};
+use crate::piece_queue::PieceQueue;
+use crate::piece_set::PieceSet;
+use crate::randomizer::RandomizerKind;
+use crate::scoring::{ClearStreak, ScoringRule, ScoringRuleKind};
+use crate::timing::auto_shift::{AutoShift, ShiftDirection};
+use crate::timing::lock_delay::LockDelay;
+use crate::timing::FRAME_DURATION;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::time::Duration;
+
+/// The rules and setup a game is started with. Delays are in frames of
+/// `timing::FRAME_DURATION`.
+#[derive(Debug, Clone)]
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub rows: usize,
+    pub cols: usize,
+    pub buffer_rows: usize,
+    pub piece_set: PieceSet,
+    pub randomizer: RandomizerKind,
+    pub seed: u64,
+    pub preview_len: usize,
+    /// How long a grounded piece waits before locking.
+    pub lock_delay: u32,
+    /// How long left or right is held before the piece starts auto shifting.
+    pub das: u32,
+    /// How often the piece auto shifts once it has started, or 0 to shift
+    /// straight to the wall.
+    pub arr: u32,
+    /// How long the next piece waits to come in after a lock.
+    pub entry_delay: u32,
+    pub scoring: ScoringRuleKind,
+    pub level_curve: LevelCurveKind,
+}
+impl Default for GameConfig {
+    fn default() -> Self {
+        let default_board = TetrisBoard::new();
+        Self {
+            mode: GameMode::Marathon,
+            rows: default_board.num_rows(),
+            cols: default_board.num_cols(),
+            buffer_rows: default_board.buffer_rows(),
+            piece_set: PieceSet::standard(),
+            randomizer: RandomizerKind::SevenBag,
+            seed: 0,
+            preview_len: 5,
+            lock_delay: LockDelay::DEFAULT_FRAMES,
+            das: AutoShift::DEFAULT_DELAY,
+            arr: AutoShift::DEFAULT_REPEAT_RATE,
+            entry_delay: 0,
+            scoring: ScoringRuleKind::Guideline,
+            level_curve: LevelCurveKind::Classic,
+        }
+    }
+}
+
+/// A game in progress. The player's commands go in through `apply` and the
+/// held shift keys through `press_shift` and `release_shift`, and time
+/// moves on through `tick`, or a frame at a time through `step_frame`. All
+/// of them report what happened as events.
+pub struct Game {
+    mode: GameMode,
+    board: TetrisBoard,
+    piece_queue: PieceQueue,
+    hold_slot: HoldSlot,
+    // The piece in play, or `None` during the entry delay and once the game
+    // is over.
+    active_piece: Option<TetrisPiece>,
+    scoring_rule: Box<dyn ScoringRule>,
+    level_curve: Box<dyn LevelCurve>,
+    clear_streak: ClearStreak,
+    // Picks where the holes go in garbage, seeded from the game's seed so
+    // that replays see the same garbage.
+    garbage_rng: StdRng,
+    lock_delay: LockDelay,
+    auto_shift: AutoShift,
+    entry_delay: u32,
+    // Fractions of a row fallen towards the next gravity step, frames spent
+    // resting on the stack, and frames left before the next piece enters.
+    gravity_progress: u32,
+    lock_frames: u32,
+    entry_frames: u32,
+    frames: u64,
+    // Time passed to `tick` that has not yet made up a whole frame.
+    unstepped: Duration,
+    // Pieces locked so far.
+    pieces: u64,
+    score: u64,
+    level: u64,
+    lines: u64,
+    lines_this_level: u64,
+    game_over: Option<GameOverReason>,
+}
+impl Game {
+    // Mixed into the game's seed for the garbage, so that the holes are not
+    // drawn from the same stream the randomizer deals the pieces from.
+    const GARBAGE_SEED_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    /// Sets up the board and deals the first piece.
+    ///
+    /// Panics if the config describes a board or preview that is out of
+    /// range, as `TetrisBoard::with_buffer` and `PieceQueue::new` do, or a
+    /// piece that does not fit the spawn area.
+    pub fn new(config: &GameConfig) -> Self {
+        let board = TetrisBoard::with_buffer(config.rows, config.cols, config.buffer_rows);
+        if let Err(message) = config.piece_set.check_fits(&board) {
+            panic!("{}", message);
+        }
+        let randomizer = config.randomizer.build(&config.piece_set, config.seed);
+        let mut game = Self {
+            mode: config.mode,
+            board,
+            piece_queue: PieceQueue::new(randomizer, config.preview_len),
+            hold_slot: HoldSlot::new(),
+            active_piece: None,
+            scoring_rule: config.scoring.build(),
+            level_curve: config.level_curve.build(),
+            clear_streak: ClearStreak::new(),
+            garbage_rng: StdRng::seed_from_u64(config.seed ^ Self::GARBAGE_SEED_MIX),
+            lock_delay: LockDelay::new(config.lock_delay, LockDelay::DEFAULT_MAX_RESETS),
+            auto_shift: AutoShift::new(config.das, config.arr),
+            entry_delay: config.entry_delay,
+            gravity_progress: 0,
+            lock_frames: 0,
+            entry_frames: 0,
+            frames: 0,
+            unstepped: Duration::ZERO,
+            pieces: 0,
+            score: 0,
+            level: 0,
+            lines: 0,
+            lines_this_level: 0,
+            game_over: None,
+        };
+        game.spawn_next(&mut Vec::new());
+        game
+    }
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+    pub fn board(&self) -> &TetrisBoard {
+        &self.board
+    }
+    pub fn active_piece(&self) -> Option<&TetrisPiece> {
+        self.active_piece.as_ref()
+    }
+    pub fn piece_queue(&self) -> &PieceQueue {
+        &self.piece_queue
+    }
+    pub fn hold_slot(&self) -> &HoldSlot {
+        &self.hold_slot
+    }
+    pub fn clear_streak(&self) -> &ClearStreak {
+        &self.clear_streak
+    }
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+    pub fn level(&self) -> u64 {
+        self.level
+    }
+    /// The total number of rows cleared this game.
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+    /// How many frames the game has run for.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+    /// The number of pieces locked so far.
+    pub fn pieces(&self) -> u64 {
+        self.pieces
+    }
+    pub fn game_over(&self) -> Option<GameOverReason> {
+        self.game_over
+    }
+    /// How long a piece takes to fall a row at the current level.
+    pub fn row_duration(&self) -> Duration {
+        self.level_curve.row_duration(self.level)
+    }
+    /// Carries out a player's command. `Command::EndGame` is left to the
+    /// front end, so it and any command while there is no piece in play do
+    /// nothing.
+    pub fn apply(&mut self, command: Command) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        let Some(piece) = self.active_piece.as_mut() else {
+            return events;
+        };
+        match command {
+            Command::EndGame => (),
+            Command::HardDrop => {
+                let piece = self.active_piece.take().unwrap();
+                let result = piece.hard_drop(&mut self.board);
+                if let Ok(rows_dropped) = result {
+                    self.score += self.scoring_rule.hard_drop_points(rows_dropped as u64);
+                }
+                self.finish_lock(result.map(|_| ()), &mut events);
+            }
+            Command::Hold => {
+                let current = piece.piece_shape();
+                let piece_queue = &mut self.piece_queue;
+                if let Some(swapped_in) = self.hold_slot.swap(current, || piece_queue.next_piece())
+                {
+                    events.push(GameEvent::PieceHeld);
+                    self.spawn(swapped_in, &mut events);
+                }
+            }
+            other_command => {
+                self.move_piece(other_command);
+            }
+        }
+        events
+    }
+    /// Starts holding a direction down. The piece shifts once straight away
+    /// and, once the DAS delay has passed, again every ARR frames until the
+    /// direction is released.
+    pub fn press_shift(&mut self, direction: ShiftDirection) {
+        self.auto_shift.press(direction);
+        self.move_piece(direction.command());
+    }
+    pub fn release_shift(&mut self, direction: ShiftDirection) {
+        self.auto_shift.release(direction);
+    }
+    /// Pushes `rows` rows of garbage up under the stack. The piece in play
+    /// moves up with the stack if it is in the way, and the game tops out
+    /// if the stack or the piece is pushed past the top of the buffer.
+    pub fn add_garbage(&mut self, rows: usize, holes: GarbageHoles) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.game_over.is_some() {
+            return events;
+        }
+        let mut result = self.board.push_garbage(rows, holes, &mut self.garbage_rng);
+        if let Some(piece) = self.active_piece.as_mut() {
+            result = result.and(piece.push_up(&self.board));
+        }
+        if let Err(reason) = result {
+            self.end(reason, &mut events);
+        }
+        events
+    }
+    /// Lets `elapsed` time pass, running a frame for each whole
+    /// `FRAME_DURATION` of it. Time short of a frame carries over to the
+    /// next call.
+    pub fn tick(&mut self, elapsed: Duration) -> Vec<GameEvent> {
+        self.unstepped += elapsed;
+        let mut events = Vec::new();
+        while self.unstepped >= FRAME_DURATION {
+            self.unstepped -= FRAME_DURATION;
+            events.extend(self.step_frame());
+        }
+        events
+    }
+    /// Moves the game on a frame: a held shift repeats, the piece falls with
+    /// gravity and locks once it has rested on the stack for the lock delay,
+    /// and after an entry delay the next piece comes in.
+    pub fn step_frame(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.game_over.is_some() {
+            return events;
+        }
+        self.frames += 1;
+        if self.mode.is_out_of_time(self.frames) {
+            self.end(GameOverReason::TimeUp, &mut events);
+            return events;
+        }
+        // Shifts charge through the entry delay, ready for the next piece.
+        let shift_cells = self.auto_shift.tick();
+        if self.active_piece.is_none() {
+            self.entry_frames = self.entry_frames.saturating_sub(1);
+            if self.entry_frames == 0 {
+                self.spawn_next(&mut events);
+            }
+            return events;
+        }
+        if let Some(direction) = self.auto_shift.held() {
+            for _ in 0..shift_cells {
+                if !self.move_piece(direction.command()) {
+                    break;
+                }
+            }
+        }
+        let piece = self.active_piece.as_mut().unwrap();
+        if piece.is_grounded(&self.board) {
+            self.lock_frames += 1;
+            if self.lock_frames >= self.lock_delay.frames() {
+                self.lock_piece(&mut events);
+            }
+            return events;
+        }
+        self.gravity_progress = self
+            .gravity_progress
+            .saturating_add(self.level_curve.gravity(self.level).per_frame());
+        while self.gravity_progress >= Gravity::ROW && piece.move_down(&self.board) {
+            self.gravity_progress -= Gravity::ROW;
+            if self.lock_delay.reach_row(piece.bottom_row()) {
+                self.lock_frames = 0;
+            }
+        }
+        if piece.is_grounded(&self.board) {
+            self.gravity_progress = 0;
+            // A piece kicked up off the stack and falling back onto it has
+            // no resets left to spend, so it locks as it lands.
+            if self.lock_delay.resets_remaining() == 0 {
+                self.lock_piece(&mut events);
+            }
+        }
+        events
+    }
+    // Moves, rotates or soft drops the piece in play, returning whether it
+    // moved. Moving a grounded piece restarts its lock delay while it has
+    // resets left, and dropping it restarts it on reaching a new lowest row.
+    fn move_piece(&mut self, command: Command) -> bool {
+        let Some(piece) = self.active_piece.as_mut() else {
+            return false;
+        };
+        let was_grounded = piece.is_grounded(&self.board);
+        let is_soft_drop = matches!(command, Command::MoveDown);
+        let Some(TurnEvent::PieceMoved) = piece.move_peice(&self.board, command) else {
+            return false;
+        };
+        if is_soft_drop {
+            self.score += self.scoring_rule.soft_drop_points(1);
+            self.gravity_progress = 0;
+            if self.lock_delay.reach_row(piece.bottom_row()) {
+                self.lock_frames = 0;
+            }
+        } else if was_grounded && self.lock_delay.try_reset() {
+            self.lock_frames = 0;
+        }
+        true
+    }
+    fn spawn_next(&mut self, events: &mut Vec<GameEvent>) {
+        let next_piece = self.piece_queue.next_piece();
+        self.spawn(next_piece, events);
+    }
+    fn spawn(&mut self, piece_shape: PieceShape, events: &mut Vec<GameEvent>) {
+        self.gravity_progress = 0;
+        self.lock_frames = 0;
+        self.lock_delay.restart();
+        match TetrisPiece::spawn(&piece_shape, &self.board) {
+            Ok(piece) => {
+                self.lock_delay.reach_row(piece.bottom_row());
+                self.active_piece = Some(piece);
+                events.push(GameEvent::PieceSpawned);
+            }
+            Err(reason) => self.end(reason, events),
+        }
+    }
+    fn lock_piece(&mut self, events: &mut Vec<GameEvent>) {
+        let piece = self.active_piece.take().unwrap();
+        let result = piece.lock(&mut self.board);
+        self.finish_lock(result, events);
+    }
+    // Scores the piece that was just fixed to the board and brings in the
+    // next one after the entry delay, unless locking it lost the game or
+    // reached the goal of the mode.
+    fn finish_lock(&mut self, result: Result<(), GameOverReason>, events: &mut Vec<GameEvent>) {
+        let line_clear = self.board.clear_rows();
+        self.pieces += 1;
+        self.score_line_clear(&line_clear);
+        events.push(GameEvent::PieceLocked(line_clear));
+        events.extend(line_clear.event());
+        if let Err(reason) = result {
+            self.end(reason, events);
+            return;
+        }
+        if self.mode.is_complete(self.lines) {
+            self.end(GameOverReason::GoalReached, events);
+            return;
+        }
+        self.hold_slot.on_piece_locked();
+        match self.entry_delay {
+            0 => self.spawn_next(events),
+            entry_delay => self.entry_frames = entry_delay,
+        }
+    }
+    fn score_line_clear(&mut self, line_clear: &LineClear) {
+        let bonus = self.clear_streak.record(line_clear);
+        self.score += self
+            .scoring_rule
+            .lock_points(line_clear, &bonus, self.level);
+        self.lines += line_clear.rows as u64;
+        self.lines_this_level += line_clear.rows as u64;
+        if self
+            .level_curve
+            .should_level_up(self.level, self.lines_this_level)
+        {
+            self.level += 1;
+            self.lines_this_level = 0;
+        }
+    }
+    fn end(&mut self, reason: GameOverReason, events: &mut Vec<GameEvent>) {
+        self.active_piece = None;
+        self.game_over = Some(reason);
+        events.push(GameEvent::GameOver(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_curve::FixedSpeed;
+
+    fn small_game() -> Game {
+        Game::new(&GameConfig {
+            rows: 6,
+            cols: 4,
+            seed: 1,
+            ..GameConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_new_game_deals_a_piece() {
+        let game = Game::new(&GameConfig::default());
+        assert!(game.active_piece().is_some());
+        assert_eq!(game.score(), 0);
+        assert_eq!(game.level(), 0);
+        assert_eq!(game.lines(), 0);
+        assert_eq!(game.game_over(), None);
+        assert_eq!(game.board().num_rows(), 16);
+    }
+
+    #[test]
+    fn test_hard_drop_locks_and_deals_next_piece() {
+        let mut game = Game::new(&GameConfig::default());
+        let next_shape = game.piece_queue().preview().next().unwrap();
+        let events = game.apply(Command::HardDrop);
+        assert!(matches!(events[0], GameEvent::PieceLocked(_)));
+        assert!(game.score() > 0);
+        assert_eq!(game.active_piece().unwrap().piece_shape(), next_shape);
+        assert!(!game.board().is_empty());
+    }
+
+    fn tick_frames(game: &mut Game, frames: u32) -> Vec<GameEvent> {
+        (0..frames).flat_map(|_| game.step_frame()).collect()
+    }
+
+    fn piece_row(game: &Game) -> i16 {
+        game.active_piece().unwrap().coordinates()[0].row
+    }
+
+    fn piece_col(game: &Game) -> i16 {
+        game.active_piece().unwrap().coordinates()[0].col
+    }
+
+    #[test]
+    fn test_gravity_moves_piece_down_with_frames() {
+        // The classic curve falls a row every 48 frames at level 0.
+        let mut game = Game::new(&GameConfig::default());
+        let start_row = piece_row(&game);
+        tick_frames(&mut game, 47);
+        assert_eq!(piece_row(&game), start_row);
+        tick_frames(&mut game, 1);
+        assert_eq!(piece_row(&game), start_row + 1);
+        tick_frames(&mut game, 96);
+        assert_eq!(piece_row(&game), start_row + 3);
+        assert_eq!(game.frames(), 144);
+    }
+
+    #[test]
+    fn test_fast_gravity_lands_piece_in_one_frame() {
+        let mut game = Game::new(&GameConfig::default());
+        game.level_curve = Box::new(FixedSpeed::new(FRAME_DURATION / 20));
+        game.step_frame();
+        assert!(game.active_piece().unwrap().is_grounded(game.board()));
+    }
+
+    #[test]
+    fn test_grounded_piece_locks_after_lock_delay() {
+        let mut game = Game::new(&GameConfig::default());
+        while !game.active_piece().unwrap().is_grounded(game.board()) {
+            game.apply(Command::MoveDown);
+        }
+        assert!(tick_frames(&mut game, 29).is_empty());
+        // Moving the piece along the floor restarts the lock delay.
+        game.apply(Command::MoveLeft);
+        assert!(tick_frames(&mut game, 29).is_empty());
+        let events = game.step_frame();
+        assert!(matches!(events[0], GameEvent::PieceLocked(_)));
+    }
+
+    #[test]
+    fn test_kicking_off_the_floor_cannot_stall_the_lock() {
+        let mut game = Game::new(&GameConfig {
+            piece_set: PieceSet::parse("[T]\ncells = -1,0 0,0 1,0 0,-1").unwrap(),
+            ..GameConfig::default()
+        });
+        while !game.active_piece().unwrap().is_grounded(game.board()) {
+            game.apply(Command::MoveDown);
+        }
+        // Turning clockwise kicks the T up off the floor and turning back
+        // leaves it in the air, to fall back onto the row it was on.
+        let mut frames = 0;
+        loop {
+            game.apply(Command::RotateClockwise);
+            game.apply(Command::RotateAnticlockwise);
+            frames += 1;
+            let events = game.step_frame();
+            if matches!(events.first(), Some(GameEvent::PieceLocked(_))) {
+                break;
+            }
+            assert!(frames < 1000, "piece never locked");
+        }
+    }
+
+    #[test]
+    fn test_held_shift_repeats_after_das() {
+        let mut game = Game::new(&GameConfig {
+            das: 5,
+            arr: 2,
+            ..GameConfig::default()
+        });
+        let start_col = piece_col(&game);
+        game.press_shift(ShiftDirection::Left);
+        assert_eq!(piece_col(&game), start_col - 1);
+        tick_frames(&mut game, 4);
+        assert_eq!(piece_col(&game), start_col - 1);
+        tick_frames(&mut game, 3);
+        assert_eq!(piece_col(&game), start_col - 3);
+        game.release_shift(ShiftDirection::Left);
+        tick_frames(&mut game, 4);
+        assert_eq!(piece_col(&game), start_col - 3);
+    }
+
+    #[test]
+    fn test_zero_arr_shifts_to_the_wall() {
+        let mut game = Game::new(&GameConfig {
+            das: 1,
+            arr: 0,
+            ..GameConfig::default()
+        });
+        game.press_shift(ShiftDirection::Right);
+        game.step_frame();
+        let piece = game.active_piece().unwrap();
+        let max_col = piece.coordinates().iter().map(|coord| coord.col).max();
+        assert_eq!(max_col, Some(game.board().num_cols() as i16 - 1));
+    }
+
+    #[test]
+    fn test_next_piece_waits_for_entry_delay() {
+        let mut game = Game::new(&GameConfig {
+            entry_delay: 3,
+            ..GameConfig::default()
+        });
+        let events = game.apply(Command::HardDrop);
+        assert!(!events.contains(&GameEvent::PieceSpawned));
+        assert!(game.active_piece().is_none());
+        assert!(game.apply(Command::MoveLeft).is_empty());
+        assert!(tick_frames(&mut game, 2).is_empty());
+        assert_eq!(game.step_frame(), vec![GameEvent::PieceSpawned]);
+        assert!(game.active_piece().is_some());
+    }
+
+    #[test]
+    fn test_hold_swaps_active_piece() {
+        let mut game = Game::new(&GameConfig::default());
+        let current = game.active_piece().unwrap().piece_shape();
+        assert_eq!(
+            game.apply(Command::Hold),
+            vec![GameEvent::PieceHeld, GameEvent::PieceSpawned]
+        );
+        assert_eq!(game.hold_slot().held(), Some(current));
+        assert!(game.apply(Command::Hold).is_empty());
+    }
+
+    #[test]
+    fn test_stacking_to_the_top_ends_the_game() {
+        let mut game = small_game();
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(game.apply(Command::HardDrop));
+        }
+        let reason = game.game_over().unwrap();
+        assert!(events.contains(&GameEvent::GameOver(reason)));
+        assert!(game.active_piece().is_none());
+        assert!(game.apply(Command::HardDrop).is_empty());
+        assert!(tick_frames(&mut game, 60).is_empty());
+    }
+
+    #[test]
+    fn test_sprint_ends_at_its_line_goal() {
+        // A bar as wide as the board clears a row with every drop.
+        let mut game = Game::new(&GameConfig {
+            mode: GameMode::Sprint { lines: 2 },
+            rows: 6,
+            cols: 4,
+            piece_set: PieceSet::parse("[I]\ncells = -1,0 0,0 1,0 2,0\ncentre = corner").unwrap(),
+            ..GameConfig::default()
+        });
+        game.apply(Command::HardDrop);
+        assert_eq!(game.lines(), 1);
+        assert_eq!(game.game_over(), None);
+        let events = game.apply(Command::HardDrop);
+        assert_eq!(
+            events.last(),
+            Some(&GameEvent::GameOver(GameOverReason::GoalReached))
+        );
+        assert_eq!(game.pieces(), 2);
+        assert!(game.active_piece().is_none());
+    }
+
+    #[test]
+    fn test_ultra_ends_when_time_is_up() {
+        // The piece never locks, so nothing but the clock ends the game.
+        let mut game = Game::new(&GameConfig {
+            mode: GameMode::Ultra { minutes: 1 },
+            lock_delay: u32::MAX,
+            ..GameConfig::default()
+        });
+        game.apply(Command::HardDrop);
+        tick_frames(&mut game, 3599);
+        assert_eq!(game.game_over(), None);
+        assert_eq!(
+            game.step_frame(),
+            vec![GameEvent::GameOver(GameOverReason::TimeUp)]
+        );
+        assert_eq!(game.frames(), 3600);
+        assert!(game.score() > 0);
+        assert!(game.active_piece().is_none());
+    }
+
+    #[test]
+    fn test_garbage_pushes_the_piece_up() {
+        let mut game = small_game();
+        let start_row = piece_row(&game);
+        // Filling the visible field reaches the piece, which starts with its
+        // bottom in the top visible row.
+        assert!(game.add_garbage(6, GarbageHoles::Random).is_empty());
+        assert_eq!(piece_row(&game), start_row - 1);
+        let events = game.add_garbage(4, GarbageHoles::Random);
+        assert_eq!(events, vec![GameEvent::GameOver(GameOverReason::TopOut)]);
+        assert_eq!(game.game_over(), Some(GameOverReason::TopOut));
+    }
+
+    #[test]
+    fn test_garbage_is_seeded_apart_from_the_pieces() {
+        let add_garbage = || {
+            let mut game = small_game();
+            game.add_garbage(4, GarbageHoles::Random);
+            game.board().rows().to_vec()
+        };
+        assert_eq!(add_garbage(), add_garbage());
+        let mut board = TetrisBoard::with_size(6, 4);
+        board
+            .push_garbage(4, GarbageHoles::Random, &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        assert_ne!(add_garbage(), board.rows());
+    }
+
+    #[test]
+    fn test_plays_with_custom_piece_set() {
+        let piece_set = PieceSet::parse(include_str!("../pieces/pentominoes.txt")).unwrap();
+        let mut game = Game::new(&GameConfig {
+            cols: 12,
+            piece_set: piece_set.clone(),
+            ..GameConfig::default()
+        });
+        // Spread the pieces against either wall so the stack stays low.
+        for piece in 0..6 {
+            let piece_shape = game.active_piece().unwrap().piece_shape();
+            assert!(piece_set.pieces().contains(&piece_shape));
+            game.apply(Command::RotateClockwise);
+            for _ in 0..6 {
+                game.apply(match piece % 2 {
+                    0 => Command::MoveLeft,
+                    _other => Command::MoveRight,
+                });
+            }
+            game.apply(Command::HardDrop);
+        }
+        assert_eq!(game.game_over(), None);
+        assert!(game.board().rows().iter().any(|row| row.count_ones() > 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_pieces_too_wide_for_board() {
+        Game::new(&GameConfig {
+            cols: 4,
+            piece_set: PieceSet::parse("[I5]\ncells = -2,0 -1,0 0,0 1,0 2,0").unwrap(),
+            ..GameConfig::default()
+        });
+    }
+
+    #[test]
+    fn test_tick_runs_a_frame_per_frame_duration() {
+        let play = |advance: &dyn Fn(&mut Game) -> Vec<GameEvent>| {
+            let mut game = small_game();
+            game.press_shift(ShiftDirection::Right);
+            let events = advance(&mut game);
+            let piece = game.active_piece().map(TetrisPiece::coordinates);
+            (events, game.frames(), piece, game.board().rows().to_vec())
+        };
+        let frames = 150;
+        assert_eq!(
+            play(&|game| game.tick(FRAME_DURATION * frames)),
+            play(&|game| tick_frames(game, frames))
+        );
+        // Time short of a frame waits for the rest of it.
+        let mut game = small_game();
+        assert!(game.tick(FRAME_DURATION / 2).is_empty());
+        assert_eq!(game.frames(), 0);
+        game.tick(FRAME_DURATION / 2 + Duration::from_nanos(1));
+        assert_eq!(game.frames(), 1);
+    }
+
+    #[test]
+    fn test_same_seed_plays_the_same_game() {
+        let play = || {
+            let mut game = small_game();
+            for command in [Command::MoveLeft, Command::HardDrop, Command::HardDrop] {
+                game.apply(command);
+            }
+            game.board().rows().to_vec()
+        };
+        assert_eq!(play(), play());
+    }
+}
@@ -0,0 +1,158 @@
+//! What a game is played for: an endless marathon, a sprint to clear a set
+//! number of lines as fast as possible, or an ultra to score as much as
+//! possible before the time runs out.
+use crate::timing::FRAMES_PER_SECOND;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameMode {
+    /// Plays on until the player tops out or quits.
+    #[default]
+    Marathon,
+    /// Ends as soon as `lines` rows have been cleared.
+    Sprint { lines: u64 },
+    /// Ends once `minutes` minutes have run.
+    Ultra { minutes: u64 },
+}
+impl GameMode {
+    /// The line goals a sprint can be run to.
+    pub const SPRINT_LINES: [u64; 3] = [20, 40, 100];
+    pub const DEFAULT_SPRINT_LINES: u64 = 40;
+    pub const DEFAULT_ULTRA_MINUTES: u64 = 2;
+    /// The longest time limit an ultra can be given.
+    pub const MAX_ULTRA_MINUTES: u64 = 60;
+
+    /// Whether clearing `lines` rows in all completes the mode.
+    pub fn is_complete(&self, lines: u64) -> bool {
+        self.lines_left(lines) == Some(0)
+    }
+    /// The rows still to clear after clearing `lines`, for modes with a
+    /// line goal.
+    pub fn lines_left(&self, lines: u64) -> Option<u64> {
+        match self {
+            GameMode::Sprint { lines: goal } => Some(goal.saturating_sub(lines)),
+            _other => None,
+        }
+    }
+    /// The frames left after `frames` have run, for modes with a time
+    /// limit.
+    pub fn frames_left(&self, frames: u64) -> Option<u64> {
+        match self {
+            GameMode::Ultra { minutes } => Some(
+                minutes
+                    .saturating_mul(60 * FRAMES_PER_SECOND)
+                    .saturating_sub(frames),
+            ),
+            _other => None,
+        }
+    }
+    /// Whether the time limit is up after `frames` have run.
+    pub fn is_out_of_time(&self, frames: u64) -> bool {
+        self.frames_left(frames) == Some(0)
+    }
+}
+impl FromStr for GameMode {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let sprint_lines = |lines: &str| {
+            lines
+                .parse()
+                .ok()
+                .filter(|lines| Self::SPRINT_LINES.contains(lines))
+        };
+        let ultra_minutes = |minutes: &str| {
+            minutes
+                .parse()
+                .ok()
+                .filter(|minutes| (1..=Self::MAX_ULTRA_MINUTES).contains(minutes))
+        };
+        let mode = match value {
+            "marathon" => Some(GameMode::Marathon),
+            "sprint" => Some(GameMode::Sprint {
+                lines: Self::DEFAULT_SPRINT_LINES,
+            }),
+            "ultra" => Some(GameMode::Ultra {
+                minutes: Self::DEFAULT_ULTRA_MINUTES,
+            }),
+            other => match other.split_once('-') {
+                Some(("sprint", lines)) => {
+                    sprint_lines(lines).map(|lines| GameMode::Sprint { lines })
+                }
+                Some(("ultra", minutes)) => {
+                    ultra_minutes(minutes).map(|minutes| GameMode::Ultra { minutes })
+                }
+                _other => None,
+            },
+        };
+        mode.ok_or_else(|| format!("Unknown game mode '{}'.", value))
+    }
+}
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameMode::Marathon => write!(f, "marathon"),
+            GameMode::Sprint { lines } => write!(f, "sprint-{}", lines),
+            GameMode::Ultra { minutes } => write!(f, "ultra-{}", minutes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_modes() {
+        assert_eq!("marathon".parse(), Ok(GameMode::Marathon));
+        assert_eq!("sprint".parse(), Ok(GameMode::Sprint { lines: 40 }));
+        assert_eq!("sprint-20".parse(), Ok(GameMode::Sprint { lines: 20 }));
+        assert_eq!("sprint-100".parse(), Ok(GameMode::Sprint { lines: 100 }));
+        assert!("sprint-30".parse::<GameMode>().is_err());
+        assert_eq!("ultra".parse(), Ok(GameMode::Ultra { minutes: 2 }));
+        assert_eq!("ultra-5".parse(), Ok(GameMode::Ultra { minutes: 5 }));
+        assert_eq!("ultra-60".parse(), Ok(GameMode::Ultra { minutes: 60 }));
+        assert!("ultra-0".parse::<GameMode>().is_err());
+        assert!("ultra-61".parse::<GameMode>().is_err());
+        assert_eq!(
+            "ultra-99999999999999999".parse::<GameMode>(),
+            Err(String::from("Unknown game mode 'ultra-99999999999999999'."))
+        );
+        assert!("zen".parse::<GameMode>().is_err());
+        for mode in [
+            GameMode::Marathon,
+            GameMode::Sprint { lines: 20 },
+            GameMode::Sprint { lines: 100 },
+            GameMode::Ultra { minutes: 3 },
+        ] {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn test_sprint_completes_at_its_line_goal() {
+        let sprint = GameMode::Sprint { lines: 20 };
+        assert_eq!(sprint.lines_left(0), Some(20));
+        assert!(!sprint.is_complete(19));
+        assert!(sprint.is_complete(20));
+        // A tetris can go past the goal.
+        assert_eq!(sprint.lines_left(22), Some(0));
+        assert!(sprint.is_complete(22));
+        assert_eq!(GameMode::Marathon.lines_left(1000), None);
+        assert!(!GameMode::Marathon.is_complete(1000));
+    }
+
+    #[test]
+    fn test_ultra_runs_out_of_time() {
+        let ultra = GameMode::Ultra { minutes: 2 };
+        assert_eq!(ultra.frames_left(0), Some(7200));
+        assert!(!ultra.is_out_of_time(7199));
+        assert!(ultra.is_out_of_time(7200));
+        assert_eq!(ultra.lines_left(0), None);
+        assert_eq!(GameMode::Sprint { lines: 40 }.frames_left(0), None);
+        assert!(!GameMode::Marathon.is_out_of_time(u64::MAX));
+        // A limit too long to count in frames saturates rather than overflows.
+        let endless = GameMode::Ultra { minutes: u64::MAX };
+        assert_eq!(endless.frames_left(0), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::models::PieceShape;
+
+/// A slot the player can set the falling piece aside in, to swap back in
+/// later. Only one hold is allowed per piece, until that piece locks.
+pub struct HoldSlot {
+    held: Option<PieceShape>,
+    can_hold: bool,
+}
+impl Default for HoldSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HoldSlot {
+    pub fn new() -> Self {
+        Self {
+            held: None,
+            can_hold: true,
+        }
+    }
+    pub fn held(&self) -> Option<PieceShape> {
+        self.held.clone()
+    }
+    pub fn can_hold(&self) -> bool {
+        self.can_hold
+    }
+    /// Puts `current` in the slot and returns the shape to play instead:
+    /// whatever was held before, or `next_piece()` if the slot was empty.
+    /// Returns `None`, leaving the slot untouched, if a piece has already
+    /// been held since the last lock.
+    pub fn swap(
+        &mut self,
+        current: PieceShape,
+        next_piece: impl FnOnce() -> PieceShape,
+    ) -> Option<PieceShape> {
+        if !self.can_hold {
+            return None;
+        }
+        self.can_hold = false;
+        Some(self.held.replace(current).unwrap_or_else(next_piece))
+    }
+    /// Allows holding again, once the piece in play has locked.
+    pub fn on_piece_locked(&mut self) {
+        self.can_hold = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_hold_takes_next_piece() {
+        let mut hold_slot = HoldSlot::new();
+        assert_eq!(
+            hold_slot.swap(PieceShape::T, || PieceShape::Bar),
+            Some(PieceShape::Bar)
+        );
+        assert_eq!(hold_slot.held(), Some(PieceShape::T));
+    }
+
+    #[test]
+    fn test_hold_swaps_with_held_piece() {
+        let mut hold_slot = HoldSlot::new();
+        hold_slot.swap(PieceShape::T, || PieceShape::Bar);
+        hold_slot.on_piece_locked();
+        assert_eq!(
+            hold_slot.swap(PieceShape::Z, || panic!("Slot was not empty.")),
+            Some(PieceShape::T)
+        );
+        assert_eq!(hold_slot.held(), Some(PieceShape::Z));
+    }
+
+    #[test]
+    fn test_hold_only_once_per_piece() {
+        let mut hold_slot = HoldSlot::new();
+        hold_slot.swap(PieceShape::T, || PieceShape::Bar);
+        assert!(!hold_slot.can_hold());
+        assert_eq!(hold_slot.swap(PieceShape::Bar, || PieceShape::Z), None);
+        assert_eq!(hold_slot.held(), Some(PieceShape::T));
+        hold_slot.on_piece_locked();
+        assert!(hold_slot.can_hold());
+    }
+}
@@ -0,0 +1,249 @@
+//! How fast pieces fall at each level, and when the level goes up.
+use crate::timing::{duration_from_frames, FRAME_DURATION};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Maps the level, counting from 0, to gravity and decides when the game
+/// moves up a level.
+pub trait LevelCurve {
+    /// How long a piece takes to fall one row at `level`.
+    fn row_duration(&self, level: u64) -> Duration;
+    /// How far a piece falls each frame at `level`.
+    fn gravity(&self, level: u64) -> Gravity {
+        Gravity::from_row_duration(self.row_duration(level))
+    }
+    /// Whether clearing `lines_this_level` rows since reaching `level` moves
+    /// the game up to the next level. Every curve levels up after 10 rows
+    /// unless it says otherwise.
+    fn should_level_up(&self, _level: u64, lines_this_level: u64) -> bool {
+        lines_this_level >= 10
+    }
+}
+
+/// How far a piece falls each frame, in `1 / Gravity::ROW`ths of a row.
+/// Slow speeds build up to a row over several frames, and fast ones fall
+/// several rows in a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Gravity(u32);
+impl Gravity {
+    /// A whole row, in the units gravity is measured in.
+    pub const ROW: u32 = 1 << 16;
+
+    /// The gravity that falls a row every `row_duration`. Rounds up, so that
+    /// a whole number of frames falls on exactly that frame.
+    pub fn from_row_duration(row_duration: Duration) -> Self {
+        let row_nanos = row_duration.as_nanos().max(1);
+        let per_frame = (Self::ROW as u128 * FRAME_DURATION.as_nanos()).div_ceil(row_nanos);
+        Self(per_frame.min(u32::MAX as u128) as u32)
+    }
+    pub fn rows_per_frame(rows: u32) -> Self {
+        Self(rows.saturating_mul(Self::ROW))
+    }
+    /// How far a piece falls each frame, in `1 / Gravity::ROW`ths of a row.
+    pub fn per_frame(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Close to the NES speeds: 5 frames faster per level up to level 8, then
+/// a frame faster per level down to a frame per row.
+pub struct Classic;
+impl LevelCurve for Classic {
+    fn row_duration(&self, level: u64) -> Duration {
+        match level {
+            0..=8 => duration_from_frames(48 - 5 * level),
+            _ => duration_from_frames(9u64.saturating_sub(level - 9).max(1)),
+        }
+    }
+}
+
+/// The frames per row from the NES game.
+pub struct NesTable;
+impl NesTable {
+    const FRAMES: [u64; 19] = [
+        48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3,
+    ];
+    const LEVEL_29: u64 = 29;
+}
+impl LevelCurve for NesTable {
+    fn row_duration(&self, level: u64) -> Duration {
+        match Self::FRAMES.get(level as usize) {
+            Some(frame_count) => duration_from_frames(*frame_count),
+            None if level < Self::LEVEL_29 => duration_from_frames(2),
+            None => duration_from_frames(1),
+        }
+    }
+}
+
+/// The guideline curve, `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds
+/// per row for guideline levels starting at 1, which levels off at level 20.
+pub struct Guideline;
+impl Guideline {
+    const MAX_LEVEL: u64 = 19;
+}
+impl LevelCurve for Guideline {
+    fn row_duration(&self, level: u64) -> Duration {
+        let level = level.min(Self::MAX_LEVEL) as f64;
+        Duration::from_secs_f64((0.8 - level * 0.007).powf(level))
+    }
+}
+
+/// Falls at the same speed whatever the level.
+pub struct FixedSpeed {
+    row_duration: Duration,
+}
+impl Default for FixedSpeed {
+    fn default() -> Self {
+        Self::new(Classic.row_duration(0))
+    }
+}
+impl FixedSpeed {
+    pub fn new(row_duration: Duration) -> Self {
+        Self { row_duration }
+    }
+}
+impl LevelCurve for FixedSpeed {
+    fn row_duration(&self, _level: u64) -> Duration {
+        self.row_duration
+    }
+}
+
+/// The level curves that can be chosen when starting a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelCurveKind {
+    Classic,
+    NesTable,
+    Guideline,
+    FixedSpeed,
+}
+impl LevelCurveKind {
+    pub fn build(&self) -> Box<dyn LevelCurve> {
+        match self {
+            LevelCurveKind::Classic => Box::new(Classic),
+            LevelCurveKind::NesTable => Box::new(NesTable),
+            LevelCurveKind::Guideline => Box::new(Guideline),
+            LevelCurveKind::FixedSpeed => Box::<FixedSpeed>::default(),
+        }
+    }
+}
+impl FromStr for LevelCurveKind {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "classic" => Ok(LevelCurveKind::Classic),
+            "nes" => Ok(LevelCurveKind::NesTable),
+            "guideline" => Ok(LevelCurveKind::Guideline),
+            "fixed" => Ok(LevelCurveKind::FixedSpeed),
+            other => Err(format!("Unknown level curve '{}'.", other)),
+        }
+    }
+}
+impl fmt::Display for LevelCurveKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LevelCurveKind::Classic => "classic",
+            LevelCurveKind::NesTable => "nes",
+            LevelCurveKind::Guideline => "guideline",
+            LevelCurveKind::FixedSpeed => "fixed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_KINDS: [LevelCurveKind; 4] = [
+        LevelCurveKind::Classic,
+        LevelCurveKind::NesTable,
+        LevelCurveKind::Guideline,
+        LevelCurveKind::FixedSpeed,
+    ];
+
+    #[test]
+    fn test_classic_curve_matches_original_formula() {
+        assert_eq!(Classic.row_duration(0), Duration::from_millis(800));
+        assert_eq!(Classic.row_duration(8), duration_from_frames(8));
+        assert_eq!(Classic.row_duration(9), Duration::from_millis(150));
+        assert_eq!(Classic.row_duration(17), FRAME_DURATION);
+        // Past the point the original formula would underflow.
+        assert_eq!(Classic.row_duration(100), FRAME_DURATION);
+    }
+
+    #[test]
+    fn test_nes_table_speeds() {
+        assert_eq!(NesTable.row_duration(0), Duration::from_millis(800));
+        assert_eq!(NesTable.row_duration(9), Duration::from_millis(100));
+        assert_eq!(NesTable.row_duration(18), Duration::from_millis(50));
+        assert_eq!(NesTable.row_duration(28), duration_from_frames(2));
+        assert_eq!(NesTable.row_duration(29), FRAME_DURATION);
+    }
+
+    #[test]
+    fn test_guideline_curve() {
+        assert_eq!(Guideline.row_duration(0), Duration::from_secs(1));
+        let level_2 = Guideline.row_duration(1).as_secs_f64();
+        assert!((level_2 - 0.793).abs() < 1e-9, "{}", level_2);
+        let level_15 = Guideline.row_duration(14).as_secs_f64();
+        assert!((level_15 - 0.00706).abs() < 1e-5, "{}", level_15);
+        assert_eq!(Guideline.row_duration(19), Guideline.row_duration(50));
+    }
+
+    #[test]
+    fn test_curves_never_slow_down() {
+        // The classic curve keeps the original formula's step back from 8
+        // frames at level 8 to 9 frames at level 9.
+        for kind in ALL_KINDS {
+            let curve = kind.build();
+            for level in 0..40 {
+                assert!(curve.row_duration(level) > Duration::ZERO);
+                if kind != LevelCurveKind::Classic {
+                    assert!(curve.row_duration(level + 1) <= curve.row_duration(level));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_gravity_of_whole_frames_per_row() {
+        assert_eq!(
+            Gravity::from_row_duration(Duration::from_millis(800)).per_frame() * 48 / Gravity::ROW,
+            1
+        );
+        assert_eq!(
+            Gravity::from_row_duration(Duration::from_millis(133)).per_frame() * 7 / Gravity::ROW,
+            0
+        );
+        assert_eq!(
+            Gravity::from_row_duration(FRAME_DURATION),
+            Gravity::rows_per_frame(1)
+        );
+        assert_eq!(
+            Gravity::from_row_duration(FRAME_DURATION / 20).per_frame() / Gravity::ROW,
+            20
+        );
+        assert_eq!(
+            Gravity::from_row_duration(Duration::ZERO).per_frame(),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_levels_up_every_ten_lines() {
+        for kind in ALL_KINDS {
+            let curve = kind.build();
+            assert!(!curve.should_level_up(0, 9));
+            assert!(curve.should_level_up(0, 10));
+        }
+    }
+
+    #[test]
+    fn test_level_curve_kind_round_trips_through_strings() {
+        for kind in ALL_KINDS {
+            assert_eq!(kind.to_string().parse::<LevelCurveKind>(), Ok(kind));
+        }
+        assert!("tgm".parse::<LevelCurveKind>().is_err());
+    }
+}
@@ -1,4 +1,14 @@
+pub mod game;
+pub mod game_mode;
+pub mod hold;
+pub mod level_curve;
 pub mod models;
-pub mod turn_timer;
+pub mod piece_queue;
+pub mod piece_set;
+pub mod randomizer;
+pub mod records;
+pub mod scoring;
+pub mod srs;
+pub mod timing;
 pub mod ui;
 pub mod views;
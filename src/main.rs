@@ -1,126 +1,374 @@
-use log;
 use std::cmp;
+use std::env;
 use std::fmt;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::thread;
+use std::time::Instant;
 
-use tetris::models::{Command, PiecePositionValidity, TetrisBoard, TetrisPiece, TurnEvent};
-use tetris::turn_timer::turn_timer::{
-    Notifier, TimerStatus, TurnTimer, TurnTimerSubscriber, TurnTimerSubscriberTrait,
-};
-use tetris::ui::{timed_user_input, CliCommandCollector};
+use tetris::game::{Game, GameConfig};
+use tetris::models::{Command, GameEvent, GameOverReason, TetrisBoard, TetrisPiece};
+use tetris::piece_queue::PieceQueue;
+use tetris::piece_set::PieceSet;
+use tetris::records::{self, BestTimes};
+use tetris::timing::auto_shift::ShiftDirection;
+use tetris::timing::{duration_from_frames, format_clock, frames_from_millis, FRAME_DURATION};
+use tetris::ui::{user_input, CliCommandCollector, KeyBindings, KeyInput};
 use tetris::views::CliView;
 
+const USAGE: &str = "Usage: tetris \
+    [--mode <marathon|sprint|sprint-20|sprint-100|ultra|ultra-<minutes>>] \
+    [--rows <rows>] [--cols <cols>] [--randomizer <random|7bag|14bag|nes>] \
+    [--seed <seed>] [--preview <1-6>] [--lock-delay <ms>] [--das <ms>] [--arr <ms>] \
+    [--entry-delay <ms>] [--scoring <nes|guideline|lines>] \
+    [--curve <classic|nes|guideline|fixed>] [--buffer <rows>] [--pieces <file>] \
+    [--rotate-180-key <key>]";
+
 fn main() {
-    println!("Game Over! Score: {}", game_runner());
+    let options = match GameOptions::from_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let (game, keys_pressed, end_game) = game_runner(&options);
+    match end_game.reason {
+        Some(GameOverReason::GoalReached) => {
+            print_sprint_result(&options.game, &game, keys_pressed)
+        }
+        Some(reason) => println!("Game Over! {}. Score: {}", reason, game.score()),
+        None => println!("Game Over! Score: {}", game.score()),
+    }
+    println!("Seed: {}", options.game.seed);
 }
-fn game_runner() -> u64 {
-    let mut tetris_board = TetrisBoard::new();
-    let mut cli_writer = CliView::<io::Stdout>::new();
-    cli_writer.draw_intro().unwrap();
-
-    let mut score = 0;
-    let mut level = 0;
-    let mut cleared_rows_count = 0;
-    loop {
-        let turn_duration = match level {
-            val if val < 9 => (1000 * (48 - (5 * level))) / 60,
-            _ => cmp::max((1000 * (9 - (level - 9))) / 60, 1000 / 60),
-        };
-        cli_writer.draw_score(score, level, turn_duration).unwrap();
 
-        let cleared_rows = match run_piece_loop(&mut tetris_board, turn_duration, &mut cli_writer) {
-            Ok(cleared_rows) => cleared_rows,
-            Err(_) => break,
-        };
-        cleared_rows_count += cleared_rows;
-        score += match cleared_rows {
-            1 => 40 * (level + 1),
-            2 => 100 * (level + 1),
-            3 => 300 * (level + 1),
-            4 => 1200 * (level + 1),
-            _other => 0,
+/// Options chosen on the command line at game start.
+struct GameOptions {
+    game: GameConfig,
+    key_bindings: KeyBindings,
+}
+impl GameOptions {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            game: GameConfig {
+                seed: rand::random(),
+                ..GameConfig::default()
+            },
+            key_bindings: KeyBindings::default(),
         };
-        if cleared_rows_count >= 10 {
-            level += 1;
-            cleared_rows_count = 0;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--mode" => options.game.mode = Self::parse_value(&arg, args.next())?,
+                "--rows" => options.game.rows = Self::parse_value(&arg, args.next())?,
+                "--cols" => options.game.cols = Self::parse_value(&arg, args.next())?,
+                "--buffer" => options.game.buffer_rows = Self::parse_value(&arg, args.next())?,
+                "--randomizer" => options.game.randomizer = Self::parse_value(&arg, args.next())?,
+                "--seed" => options.game.seed = Self::parse_value(&arg, args.next())?,
+                "--preview" => options.game.preview_len = Self::parse_value(&arg, args.next())?,
+                "--lock-delay" => options.game.lock_delay = Self::parse_millis(&arg, args.next())?,
+                "--das" => options.game.das = Self::parse_millis(&arg, args.next())?,
+                "--arr" => options.game.arr = Self::parse_millis(&arg, args.next())?,
+                "--entry-delay" => {
+                    options.game.entry_delay = Self::parse_millis(&arg, args.next())?
+                }
+                "--scoring" => options.game.scoring = Self::parse_value(&arg, args.next())?,
+                "--curve" => options.game.level_curve = Self::parse_value(&arg, args.next())?,
+                "--pieces" => {
+                    let path: String = Self::parse_value(&arg, args.next())?;
+                    options.game.piece_set = PieceSet::load(Path::new(&path))?
+                }
+                "--rotate-180-key" => {
+                    options.key_bindings.rotate_180 = Self::parse_value(&arg, args.next())?
+                }
+                other => return Err(format!("Unrecognised argument '{}'.", other)),
+            }
+        }
+        if !(PieceQueue::MIN_PREVIEW_LEN..=PieceQueue::MAX_PREVIEW_LEN)
+            .contains(&options.game.preview_len)
+        {
+            return Err(format!(
+                "The preview must show between {} and {} pieces.",
+                PieceQueue::MIN_PREVIEW_LEN,
+                PieceQueue::MAX_PREVIEW_LEN
+            ));
+        }
+        if options.game.cols > TetrisBoard::MAX_NUM_COLS {
+            return Err(format!(
+                "The board can be at most {} columns wide.",
+                TetrisBoard::MAX_NUM_COLS
+            ));
         }
+        if options.game.rows < TetrisBoard::MIN_NUM_ROWS
+            || options.game.cols < TetrisBoard::MIN_NUM_COLS
+        {
+            return Err(format!(
+                "The board must be at least {}x{}.",
+                TetrisBoard::MIN_NUM_COLS,
+                TetrisBoard::MIN_NUM_ROWS
+            ));
+        }
+        if options.game.buffer_rows < TetrisBoard::MIN_BUFFER_ROWS {
+            return Err(format!(
+                "The buffer must be at least {} rows.",
+                TetrisBoard::MIN_BUFFER_ROWS
+            ));
+        }
+        // A key can only issue one command, so sharing one would leave either
+        // the 180 rotation or the other command out of reach.
+        let key_bindings = options.key_bindings;
+        let other_keys = [
+            key_bindings.rotate_clockwise,
+            key_bindings.rotate_anticlockwise,
+            key_bindings.hold,
+            ' ',
+        ];
+        if other_keys.contains(&key_bindings.rotate_180) {
+            return Err(format!(
+                "The 180 rotation key '{}' is already bound to another command.",
+                key_bindings.rotate_180
+            ));
+        }
+        options
+            .game
+            .piece_set
+            .check_fits(&TetrisBoard::with_buffer(
+                options.game.rows,
+                options.game.cols,
+                options.game.buffer_rows,
+            ))?;
+        Ok(options)
+    }
+    // Delays are given in milliseconds and counted in frames.
+    fn parse_millis(flag: &str, value: Option<String>) -> Result<u32, String> {
+        Self::parse_value(flag, value)
+            .ok()
+            .and_then(frames_from_millis)
+            .ok_or_else(|| format!("Invalid or missing value for '{}'.", flag))
+    }
+    fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+        value
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Invalid or missing value for '{}'.", flag))
     }
-    score
 }
-fn run_piece_loop(
-    tetris_board: &mut TetrisBoard,
-    turn_duration: u64,
+
+/// Plays a game through, returning it as it ended along with the number of
+/// keys pressed and why it ended.
+fn game_runner(options: &GameOptions) -> (Game, u64, EndGameError) {
+    let mut game = Game::new(&options.game);
+    let mut cli_writer = CliView::<io::Stdout>::new(game.board());
+    cli_writer.fit_previews(&options.game.piece_set);
+    cli_writer.draw_intro(&options.key_bindings).unwrap();
+    cli_writer
+        .draw_hold(game.hold_slot().held())
+        .expect("Failed to draw held piece.");
+    cli_writer
+        .draw_next(game.piece_queue().preview())
+        .expect("Failed to draw next pieces.");
+    draw_score(&game, &mut cli_writer);
+    let mut keys_pressed = 0;
+    let stop_input = AtomicBool::new(false);
+    let end_game = thread::scope(|s| {
+        let (input_dispatcher, input_reciever) = mpsc::channel();
+        user_input::<CliCommandCollector>(input_dispatcher, &stop_input, options.key_bindings, s);
+        let end_game = run_frames(
+            &mut game,
+            &mut cli_writer,
+            &input_reciever,
+            &mut keys_pressed,
+        );
+        stop_input.store(true, Ordering::Relaxed);
+        end_game
+    });
+    (game, keys_pressed, end_game)
+}
+
+/// Runs the game a frame at a time at a fixed rate until it ends: the keys
+/// pressed since the last frame go in, the game steps a frame, and whatever changed
+/// is drawn. Every key pressed is counted in `keys_pressed`.
+fn run_frames(
+    game: &mut Game,
     cli_writer: &mut CliView<io::Stdout>,
-) -> Result<u16, EndGameError> {
-    let mut tetris_piece = TetrisPiece::new(tetris::models::PieceShape::random());
-    if let PiecePositionValidity::PieceCollision =
-        tetris_board.check_is_valid_position(&tetris_piece.coordinates())
-    {
-        return Err(EndGameError);
-    }
+    input_reciever: &mpsc::Receiver<KeyInput>,
+    keys_pressed: &mut u64,
+) -> EndGameError {
+    let mut next_frame = Instant::now();
+    let mut drawn_piece = None;
     loop {
-        cli_writer
-            .draw_piece_and_board(&tetris_piece, &tetris_board)
-            .expect("Failed to draw board.");
-
-        let mut turn_timer = TurnTimer::new(turn_duration);
-        let mut turn_timer_subscriber = TurnTimerSubscriber::new();
-        let mut turn_timer_subscriber_1 = TurnTimerSubscriber::new();
-        turn_timer.add_subscriber(&mut turn_timer_subscriber);
-        turn_timer.add_subscriber(&mut turn_timer_subscriber_1);
-
-        turn_timer.run_timer();
-        thread::scope(|s| {
-            let (command_dispatcher, command_reciever) = mpsc::channel();
-            let (turn_event_sender, turn_event_reciever) = mpsc::channel::<TurnEvent>();
-            timed_user_input::<CliCommandCollector, TurnTimerSubscriber>(
-                turn_timer_subscriber,
-                command_dispatcher,
-                turn_event_reciever,
-                s,
-            );
+        let mut events = Vec::new();
+        for key_input in input_reciever.try_iter() {
+            if let KeyInput::Pressed(Command::EndGame) | KeyInput::Tapped(Command::EndGame) =
+                key_input
+            {
+                return EndGameError::quit();
+            }
+            if let KeyInput::Pressed(_) | KeyInput::Tapped(_) = key_input {
+                *keys_pressed += 1;
+            }
+            events.extend(apply_key_input(game, key_input));
+        }
+        events.extend(game.step_frame());
+        if let Some(reason) = game.game_over() {
+            return reason.into();
+        }
+        draw_events(game, cli_writer, &events);
+        if game.mode().frames_left(game.frames()).is_some() {
+            draw_score(game, cli_writer);
+        }
+        if let Some(lines_left) = game.mode().lines_left(game.lines()) {
+            cli_writer
+                .draw_timer(duration_from_frames(game.frames()), lines_left)
+                .expect("Failed to draw timer.");
+        }
+        let piece_coordinates = game.active_piece().map(TetrisPiece::coordinates);
+        if !events.is_empty() || piece_coordinates != drawn_piece {
+            cli_writer
+                .draw_piece_and_board(game.active_piece(), game.board())
+                .expect("Failed to draw board.");
+            drawn_piece = piece_coordinates;
+        }
+        // Frames keep to a fixed schedule so a slow one doesn't push back
+        // the rest, but a game that falls behind picks up from now rather
+        // than rushing to catch up.
+        next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        match next_frame.checked_duration_since(now) {
+            Some(wait) => thread::sleep(wait),
+            None => next_frame = now,
+        }
+    }
+}
 
-            for recieved in command_reciever {
-                if let TimerStatus::TimerComplete = turn_timer_subscriber_1.get_timer_status() {
-                    break;
-                }
-                match recieved {
-                    Command::EndGame => {
-                        return Err(EndGameError);
-                    }
-                    other_command => {
-                        if let Some(TurnEvent::EndTurn) =
-                            tetris_piece.move_peice(&tetris_board, other_command)
-                        {
-                            if let Err(_) = turn_event_sender.send(TurnEvent::EndTurn) {
-                                log::warn!("End turn event sent to closed turn event channel.");
-                            };
-                            break;
-                        };
-                        cli_writer
-                            .draw_piece_and_board(&tetris_piece, &tetris_board)
-                            .expect("Failed to draw board.");
-                    }
-                }
+/// Passes a key on to the game. Held shifts repeat by the game's own DAS,
+/// and drops, rotations and holds act once per press, so the terminal's
+/// repeats are dropped for all but soft drop.
+fn apply_key_input(game: &mut Game, key_input: KeyInput) -> Vec<GameEvent> {
+    match key_input {
+        KeyInput::Pressed(command) => match ShiftDirection::for_command(&command) {
+            Some(direction) => {
+                game.press_shift(direction);
+                Vec::new()
             }
-            Ok(())
-        })?;
-        if let Some(out_piece) = tetris_piece.move_down(tetris_board) {
-            tetris_piece = out_piece;
-        } else {
-            break;
+            None => game.apply(command),
+        },
+        KeyInput::Released(command) => {
+            if let Some(direction) = ShiftDirection::for_command(&command) {
+                game.release_shift(direction);
+            }
+            Vec::new()
         }
+        KeyInput::Repeated(Command::MoveDown) => game.apply(Command::MoveDown),
+        KeyInput::Repeated(_command) => Vec::new(),
+        KeyInput::Tapped(command) => game.apply(command),
     }
-    Ok(tetris_board.clear_rows())
 }
 
-struct EndGameError;
+fn draw_events(game: &Game, cli_writer: &mut CliView<io::Stdout>, events: &[GameEvent]) {
+    for event in events {
+        match event {
+            GameEvent::PieceLocked(line_clear) => {
+                cli_writer
+                    .draw_event(line_clear.event())
+                    .expect("Failed to draw event banner.");
+                draw_score(game, cli_writer);
+            }
+            GameEvent::PieceSpawned => {
+                cli_writer
+                    .draw_next(game.piece_queue().preview())
+                    .expect("Failed to draw next pieces.");
+            }
+            GameEvent::PieceHeld => {
+                cli_writer
+                    .draw_hold(game.hold_slot().held())
+                    .expect("Failed to draw held piece.");
+            }
+            _other => (),
+        }
+    }
+}
+
+fn draw_score(game: &Game, cli_writer: &mut CliView<io::Stdout>) {
+    cli_writer
+        .draw_score(
+            game.score(),
+            game.level(),
+            // Speeds of more than a row a millisecond show as 1 ms.
+            cmp::max(game.row_duration().as_millis() as u64, 1),
+            game.clear_streak(),
+            game.mode()
+                .frames_left(game.frames())
+                .map(duration_from_frames),
+        )
+        .expect("Failed to draw score.");
+}
+
+/// Prints the time a finished sprint took and how it places among the best
+/// times kept for its mode and setup.
+fn print_sprint_result(config: &GameConfig, game: &Game, keys_pressed: u64) {
+    let time = duration_from_frames(game.frames());
+    println!("Sprint complete! Time: {}", format_clock(time));
+    println!(
+        "Pieces per second: {:.2}",
+        game.pieces() as f64 / time.as_secs_f64()
+    );
+    println!("Keys pressed: {}", keys_pressed);
+    let Some(path) = best_times_path() else {
+        return;
+    };
+    let mut best_times = match BestTimes::load(&path) {
+        Ok(best_times) => best_times,
+        Err(message) => {
+            eprintln!("{}", message);
+            return;
+        }
+    };
+    let list = records::list_name(config);
+    let place = best_times.record(&list, time);
+    if let Err(message) = best_times.save(&path) {
+        eprintln!("{}", message);
+    }
+    println!("Best times for {}:", list);
+    for (index, best) in best_times.times(&list).iter().enumerate() {
+        let new_best = match place == Some(index + 1) {
+            true => " (new)",
+            false => "",
+        };
+        println!("{:>2}. {}{}", index + 1, format_clock(*best), new_best);
+    }
+}
+
+/// The best times are kept in the player's home directory, if they have one.
+fn best_times_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".tetris_best_times"))
+}
+
+/// Why the game ended: the player quit, or lost for the given reason.
+struct EndGameError {
+    reason: Option<GameOverReason>,
+}
+impl EndGameError {
+    fn quit() -> Self {
+        Self { reason: None }
+    }
+}
+impl From<GameOverReason> for EndGameError {
+    fn from(reason: GameOverReason) -> Self {
+        Self {
+            reason: Some(reason),
+        }
+    }
+}
 
 impl fmt::Display for EndGameError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Ending game.") // user-facing output
+        match self.reason {
+            Some(reason) => write!(f, "{}.", reason), // user-facing output
+            None => write!(f, "Ending game."),
+        }
     }
 }
 
@@ -129,3 +377,60 @@ impl fmt::Debug for EndGameError {
         write!(f, "{{ file: {}, line: {} }}", file!(), line!()) // programmer-facing output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| String::from(*arg))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_rejects_rotate_180_key_bound_to_another_command() {
+        for key in ["x", "z", "c", " "] {
+            assert_eq!(
+                GameOptions::from_args(args(&["--rotate-180-key", key])).err(),
+                Some(format!(
+                    "The 180 rotation key '{}' is already bound to another command.",
+                    key
+                ))
+            );
+        }
+        let options = GameOptions::from_args(args(&["--rotate-180-key", "s"])).unwrap();
+        assert_eq!(options.key_bindings.rotate_180, 's');
+    }
+
+    #[test]
+    fn test_only_soft_drop_acts_on_key_repeats() {
+        let mut game = Game::new(&GameConfig::default());
+        let piece = game.active_piece().unwrap().coordinates();
+        for command in [
+            Command::HardDrop,
+            Command::RotateClockwise,
+            Command::Hold,
+            Command::MoveLeft,
+        ] {
+            assert!(apply_key_input(&mut game, KeyInput::Repeated(command)).is_empty());
+        }
+        assert_eq!(game.active_piece().unwrap().coordinates(), piece);
+        apply_key_input(&mut game, KeyInput::Repeated(Command::MoveDown));
+        let dropped = game.active_piece().unwrap().coordinates();
+        assert_eq!(dropped[0].row, piece[0].row + 1);
+    }
+
+    #[test]
+    fn test_rejects_delays_too_long_to_count_in_frames() {
+        let options = GameOptions::from_args(args(&["--das", "1000"])).unwrap();
+        assert_eq!(options.game.das, 60);
+        for delay in ["18446744073709551615", "71582788267"] {
+            assert_eq!(
+                GameOptions::from_args(args(&["--das", delay])).err(),
+                Some(String::from("Invalid or missing value for '--das'."))
+            );
+        }
+    }
+}
@@ -1,63 +1,344 @@
-use rand::seq::IteratorRandom;
+use crate::piece_set::{PieceColour, PieceDefinition, RotationCentre};
+use crate::srs::{self, RotationState};
+use rand::Rng;
+use std::fmt;
 use std::slice::Iter;
+use std::sync::Arc;
 
+/// A row of the board stored as a bitmask, with bit `n` set when column `n`
+/// is occupied.
+pub type RowMask = u64;
+
+/// The playfield. Rows are numbered from the top of the visible field, so
+/// the hidden buffer rows above it, where pieces spawn, have negative row
+/// numbers.
 pub struct TetrisBoard {
-    pub board: Vec<Vec<bool>>,
+    // Every row including the buffer, top row first.
+    rows: Vec<RowMask>,
+    // What fills each cell, row by row. The row masks mirror this for fast
+    // collision checks.
+    cells: Vec<Cell>,
+    num_rows: usize,
+    num_cols: usize,
+    buffer_rows: usize,
+    full_row: RowMask,
+    // The T-spin, if any, made by the piece locked since rows were last cleared.
+    locked_t_spin: TSpin,
+}
+impl Default for TetrisBoard {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl TetrisBoard {
-    const NUM_ROWS: usize = 16;
-    const NUM_COLS: usize = 10;
+    const DEFAULT_NUM_ROWS: usize = 16;
+    const DEFAULT_NUM_COLS: usize = 10;
+    pub const DEFAULT_BUFFER_ROWS: usize = 4;
+    pub const MIN_NUM_ROWS: usize = 4;
+    // Every piece must fit in the spawn area, the widest being the four cell
+    // bar and the tallest the two row pieces.
+    pub const MIN_NUM_COLS: usize = 4;
+    pub const MIN_BUFFER_ROWS: usize = 2;
+    pub const MAX_NUM_COLS: usize = RowMask::BITS as usize;
+    /// Tallest piece that can be collision checked in one pass of row masks.
+    pub const MAX_PIECE_ROWS: usize = 8;
 
     pub fn new() -> Self {
-        let row = vec![false; Self::NUM_COLS];
+        Self::with_size(Self::DEFAULT_NUM_ROWS, Self::DEFAULT_NUM_COLS)
+    }
+    /// Creates an empty board with the given number of visible rows and
+    /// columns, and the default hidden buffer above it.
+    ///
+    /// Panics if the board is too small to spawn a piece in, i.e. smaller
+    /// than 4 rows by 4 columns, or wider than fits in a `RowMask`.
+    pub fn with_size(num_rows: usize, num_cols: usize) -> Self {
+        Self::with_buffer(num_rows, num_cols, Self::DEFAULT_BUFFER_ROWS)
+    }
+    /// Creates an empty board with `buffer_rows` hidden rows above the
+    /// visible ones.
+    ///
+    /// Panics as `with_size` does, or if the buffer is too short for pieces
+    /// to spawn in.
+    pub fn with_buffer(num_rows: usize, num_cols: usize, buffer_rows: usize) -> Self {
+        assert!(
+            num_rows >= Self::MIN_NUM_ROWS && num_cols >= Self::MIN_NUM_COLS,
+            "Board must be at least {}x{}, got {}x{}.",
+            Self::MIN_NUM_COLS,
+            Self::MIN_NUM_ROWS,
+            num_cols,
+            num_rows
+        );
+        assert!(
+            num_cols <= Self::MAX_NUM_COLS,
+            "Board can be at most {} columns wide, got {}.",
+            Self::MAX_NUM_COLS,
+            num_cols
+        );
+        assert!(
+            buffer_rows >= Self::MIN_BUFFER_ROWS,
+            "Buffer must be at least {} rows, got {}.",
+            Self::MIN_BUFFER_ROWS,
+            buffer_rows
+        );
+        let total_rows = buffer_rows + num_rows;
         Self {
-            board: vec![row; Self::NUM_ROWS],
+            rows: vec![0; total_rows],
+            cells: vec![Cell::Empty; total_rows * num_cols],
+            num_rows,
+            num_cols,
+            buffer_rows,
+            full_row: RowMask::MAX >> (Self::MAX_NUM_COLS - num_cols),
+            locked_t_spin: TSpin::None,
         }
     }
+    /// The number of visible rows.
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+    pub fn num_cols(&self) -> usize {
+        self.num_cols
+    }
+    /// The number of hidden rows above the visible field.
+    pub fn buffer_rows(&self) -> usize {
+        self.buffer_rows
+    }
+    /// The occupied cells of every visible row, top row first.
+    pub fn rows(&self) -> &[RowMask] {
+        &self.rows[self.buffer_rows..]
+    }
+    // Where a row, counted from the top of the visible field, is stored.
+    fn row_index(&self, row: i16) -> usize {
+        (row + self.buffer_rows as i16) as usize
+    }
+    pub fn is_row_full(&self, row: usize) -> bool {
+        self.rows[self.row_index(row as i16)] == self.full_row
+    }
+    pub fn is_occupied(&self, row: usize, col: usize) -> bool {
+        self.rows[self.row_index(row as i16)] & (1 << col) != 0
+    }
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        self.cells[self.row_index(row as i16) * self.num_cols + col].clone()
+    }
+    pub fn set_cell(&mut self, row: usize, col: usize, cell: Cell) {
+        self.fill(
+            &Coord {
+                col: col as i16,
+                row: row as i16,
+            },
+            cell,
+        );
+    }
+    // Sets any cell on the board, including those in the buffer.
+    fn fill(&mut self, coord: &Coord, cell: Cell) {
+        let row_index = self.row_index(coord.row);
+        match &cell {
+            Cell::Empty => self.rows[row_index] &= !(1 << coord.col),
+            _filled => self.rows[row_index] |= 1 << coord.col,
+        }
+        self.cells[row_index * self.num_cols + coord.col as usize] = cell;
+    }
+    /// The centre a piece is spawned at: the middle column (rounding left on
+    /// even widths), with the piece sat in the buffer just above the
+    /// visible field.
+    pub fn spawn_centre(&self, piece_shape: &PieceShape) -> Coord {
+        let bottom = piece_shape.shape().iter().map(|coord| coord.row).max();
+        Coord {
+            col: (self.num_cols as i16 - 1) / 2,
+            row: -1 - bottom.unwrap_or(0),
+        }
+    }
+    /// Whether a piece spawned in its spawn orientation lies wholly on the
+    /// board, within its width and the buffer.
+    pub fn fits_spawn_area(&self, piece_shape: &PieceShape) -> bool {
+        let centre = self.spawn_centre(piece_shape);
+        let coordinates = piece_shape
+            .shape()
+            .iter()
+            .map(|coord| Coord {
+                col: coord.col + centre.col,
+                row: coord.row + centre.row,
+            })
+            .collect();
+        self.check_coordinates_on_board(&coordinates)
+    }
     fn check_coordinates_on_board(&self, coordinates: &Vec<Coord>) -> bool {
         for coord in coordinates {
-            if coord.row < 0
-                || coord.row >= Self::NUM_ROWS as i16
+            if coord.row < -(self.buffer_rows as i16)
+                || coord.row >= self.num_rows as i16
                 || coord.col < 0
-                || coord.col >= Self::NUM_COLS as i16
+                || coord.col >= self.num_cols as i16
             {
                 return false;
             }
         }
-        return true;
+        true
     }
     pub fn check_is_valid_position(&self, coordinates: &Vec<Coord>) -> PiecePositionValidity {
         if !self.check_coordinates_on_board(coordinates) {
             return PiecePositionValidity::OffOfBoard;
         }
+        if self.collides(coordinates) {
+            return PiecePositionValidity::PieceCollision;
+        }
+        PiecePositionValidity::Valid
+    }
+    // Folds the piece into one mask per row it covers so that each row of the
+    // board is tested with a single AND. Coordinates must be on the board.
+    fn collides(&self, coordinates: &[Coord]) -> bool {
+        let Some(top_row) = coordinates.iter().map(|coord| coord.row).min() else {
+            return false;
+        };
+        let mut piece_masks: [RowMask; Self::MAX_PIECE_ROWS] = [0; Self::MAX_PIECE_ROWS];
         for coord in coordinates {
-            if self.board[coord.row as usize][coord.col as usize] {
-                return PiecePositionValidity::PieceCollision;
-            }
+            piece_masks[(coord.row - top_row) as usize] |= 1 << coord.col;
         }
-        return PiecePositionValidity::Valid;
+        piece_masks
+            .iter()
+            .zip(&self.rows[self.row_index(top_row)..])
+            .any(|(piece_mask, board_row)| piece_mask & board_row != 0)
     }
 
     fn fix_piece_in_place(&mut self, piece: TetrisPiece) {
+        self.locked_t_spin = piece.t_spin(self);
+        let cell = Cell::Piece(piece.piece_shape());
         for coord in piece.coordinates() {
-            self.board[coord.row as usize][coord.col as usize] = true;
+            self.fill(&coord, cell.clone());
         }
     }
-    pub fn clear_rows(&mut self) -> u16 {
-        let mut board_without_row: Vec<Vec<bool>> = self
-            .board
-            .clone()
-            .into_iter()
-            .rev()
-            .filter(|row| !row.iter().all(|x| *x))
-            .collect();
-        let num_cleared_rows = Self::NUM_ROWS - board_without_row.len();
-        for _ in 0..num_cleared_rows {
-            board_without_row.push(vec![false; Self::NUM_COLS]);
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+    /// Removes every full row, shifting the rows above down in place, and
+    /// reports how many were removed along with any T-spin made by the piece
+    /// that was just locked, and whether it left the board empty.
+    pub fn clear_rows(&mut self) -> LineClear {
+        let mut write_row = self.rows.len();
+        for read_row in (0..self.rows.len()).rev() {
+            if self.rows[read_row] != self.full_row {
+                write_row -= 1;
+                self.rows[write_row] = self.rows[read_row];
+                // Swapped rather than copied, as cells can share a custom
+                // piece's definition. Whatever lands in the read row is
+                // written over or emptied later on.
+                for col in 0..self.num_cols {
+                    self.cells.swap(
+                        read_row * self.num_cols + col,
+                        write_row * self.num_cols + col,
+                    );
+                }
+            }
+        }
+        self.rows[..write_row].fill(0);
+        self.cells[..write_row * self.num_cols].fill(Cell::Empty);
+        LineClear {
+            rows: write_row as u16,
+            t_spin: std::mem::replace(&mut self.locked_t_spin, TSpin::None),
+            perfect_clear: write_row > 0 && self.is_empty(),
         }
-        self.board = board_without_row.into_iter().rev().collect();
-        return num_cleared_rows as u16;
     }
+    /// Pushes `rows` rows of garbage in from the bottom, each full but for a
+    /// single hole placed as `holes` says, and moves everything else up to
+    /// make room. The board tops out if that pushes any of the stack off the
+    /// top of the buffer.
+    pub fn push_garbage(
+        &mut self,
+        rows: usize,
+        holes: GarbageHoles,
+        rng: &mut impl Rng,
+    ) -> Result<(), GameOverReason> {
+        let rows = rows.min(self.rows.len());
+        let topped_out = self.rows[..rows].iter().any(|row| *row != 0);
+        self.rows.copy_within(rows.., 0);
+        // The rows rotated round to the bottom are filled with garbage below.
+        self.cells.rotate_left(rows * self.num_cols);
+        let mut hole = rng.gen_range(0..self.num_cols);
+        for row_index in self.rows.len() - rows..self.rows.len() {
+            hole = match holes {
+                GarbageHoles::SingleColumn => hole,
+                GarbageHoles::Random => rng.gen_range(0..self.num_cols),
+                GarbageHoles::Messy { change_percent }
+                    if rng.gen_range(0..100) < change_percent =>
+                {
+                    // Any column but the one the hole is in now.
+                    let new_hole = rng.gen_range(0..self.num_cols - 1);
+                    new_hole + (new_hole >= hole) as usize
+                }
+                GarbageHoles::Messy { .. } => hole,
+            };
+            self.rows[row_index] = self.full_row & !(1 << hole);
+            let row_cells = &mut self.cells[row_index * self.num_cols..][..self.num_cols];
+            row_cells.fill(Cell::Garbage);
+            row_cells[hole] = Cell::Empty;
+        }
+        match topped_out {
+            true => Err(GameOverReason::TopOut),
+            false => Ok(()),
+        }
+    }
+}
+/// Where the hole goes in each row of garbage pushed onto the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarbageHoles {
+    /// Every row has its hole in the same randomly chosen column, leaving a
+    /// well to clear them all down.
+    SingleColumn,
+    /// Every row has its hole in a random column.
+    Random,
+    /// Each row moves the hole to another random column `change_percent`
+    /// percent of the time, and otherwise keeps it under the row above.
+    Messy { change_percent: u8 },
+}
+/// What locking a piece did to the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineClear {
+    pub rows: u16,
+    pub t_spin: TSpin,
+    /// The clear left nothing at all on the board.
+    pub perfect_clear: bool,
+}
+impl LineClear {
+    /// The event worth announcing to the player, if the clear made one.
+    pub fn event(&self) -> Option<GameEvent> {
+        match self.perfect_clear {
+            true => Some(GameEvent::PerfectClear),
+            false => None,
+        }
+    }
+}
+/// Things that happen during a game that a front end may want to show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    /// A piece was fixed to the board, clearing any full rows.
+    PieceLocked(LineClear),
+    /// The last lock left the board empty.
+    PerfectClear,
+    /// The piece in play was swapped with the hold slot.
+    PieceHeld,
+    /// A new piece came into play, from the queue or the hold slot.
+    PieceSpawned,
+    GameOver(GameOverReason),
+}
+/// A T piece locked after rotating into a slot with at least three of the
+/// four corners around its centre filled. It is a full T-spin when both
+/// corners either side of the point of the T are filled, or when the last
+/// rotation needed the final SRS kick, and a mini otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+/// What fills a cell of the board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    /// Left behind by a piece the player locked.
+    Piece(PieceShape),
+    /// Added to the stack by the game rather than placed by the player.
+    Garbage,
+    /// Reserved for modes that give some blocks their own behaviour.
+    Special,
 }
 #[derive(Debug, PartialEq)]
 pub enum PiecePositionValidity {
@@ -65,7 +346,7 @@ pub enum PiecePositionValidity {
     OffOfBoard,
     PieceCollision,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PieceShape {
     Square,
     Bar,
@@ -74,10 +355,17 @@ pub enum PieceShape {
     L,
     FlippedL,
     T,
+    /// A piece from a set loaded at start up, shared by every copy of the
+    /// set.
+    Custom(Arc<PieceDefinition>),
 }
 impl PieceShape {
+    /// The cells of the piece in its spawn orientation, relative to the
+    /// piece centre. These follow the SRS spawn orientations, with the flat
+    /// side of every piece but the square and bar facing down.
     pub fn shape(&self) -> Vec<Coord> {
-        match *self {
+        match self {
+            PieceShape::Custom(definition) => definition.cells.clone(),
             PieceShape::Square => vec![
                 Coord { col: 0, row: 0 },
                 Coord { col: 0, row: 1 },
@@ -91,37 +379,63 @@ impl PieceShape {
                 Coord { col: 2, row: 0 },
             ],
             PieceShape::Z => vec![
-                Coord { col: -1, row: 0 },
+                Coord { col: -1, row: -1 },
+                Coord { col: 0, row: -1 },
                 Coord { col: 0, row: 0 },
-                Coord { col: 0, row: 1 },
-                Coord { col: 1, row: 1 },
+                Coord { col: 1, row: 0 },
             ],
             PieceShape::FlippedZ => vec![
-                Coord { col: -1, row: 1 },
+                Coord { col: -1, row: 0 },
                 Coord { col: 0, row: 0 },
-                Coord { col: 0, row: 1 },
-                Coord { col: 1, row: 0 },
+                Coord { col: 0, row: -1 },
+                Coord { col: 1, row: -1 },
             ],
             PieceShape::L => vec![
-                Coord { col: -1, row: 1 },
-                Coord { col: 0, row: 1 },
-                Coord { col: 1, row: 1 },
+                Coord { col: -1, row: 0 },
+                Coord { col: 0, row: 0 },
                 Coord { col: 1, row: 0 },
+                Coord { col: 1, row: -1 },
             ],
             PieceShape::FlippedL => vec![
+                Coord { col: -1, row: -1 },
                 Coord { col: -1, row: 0 },
-                Coord { col: -1, row: 1 },
-                Coord { col: 0, row: 1 },
-                Coord { col: 1, row: 1 },
+                Coord { col: 0, row: 0 },
+                Coord { col: 1, row: 0 },
             ],
             PieceShape::T => vec![
-                Coord { col: -1, row: 1 },
-                Coord { col: 0, row: 1 },
+                Coord { col: -1, row: 0 },
                 Coord { col: 0, row: 0 },
-                Coord { col: 1, row: 1 },
+                Coord { col: 0, row: -1 },
+                Coord { col: 1, row: 0 },
             ],
         }
     }
+    /// Whether the piece rotates about the corner shared by its centre cell
+    /// and the cells below and to the right of it, rather than about the
+    /// middle of the centre cell. This is how the even sided square and bar
+    /// turn in place.
+    pub fn rotates_about_cell_corner(&self) -> bool {
+        match self {
+            PieceShape::Square | PieceShape::Bar => true,
+            PieceShape::Custom(definition) => definition.rotation_centre == RotationCentre::Corner,
+            _other => false,
+        }
+    }
+    /// The guideline colour of each tetromino, or the colour a custom piece
+    /// was given.
+    pub fn colour(&self) -> PieceColour {
+        match self {
+            PieceShape::Square => PieceColour::Yellow,
+            PieceShape::Bar => PieceColour::Cyan,
+            PieceShape::Z => PieceColour::Red,
+            PieceShape::FlippedZ => PieceColour::Green,
+            PieceShape::L => PieceColour::Orange,
+            PieceShape::FlippedL => PieceColour::Blue,
+            PieceShape::T => PieceColour::Magenta,
+            PieceShape::Custom(definition) => definition.colour,
+        }
+    }
+    /// The seven tetrominoes.
     pub fn iterator() -> Iter<'static, Self> {
         static PIECE_SHAPES: [PieceShape; 7] = [
             PieceShape::Square,
@@ -134,9 +448,6 @@ impl PieceShape {
         ];
         PIECE_SHAPES.iter()
     }
-    pub fn random() -> &'static Self {
-        Self::iterator().choose(&mut rand::thread_rng()).unwrap()
-    }
 }
 #[derive(Debug)]
 pub enum Command {
@@ -145,25 +456,63 @@ pub enum Command {
     MoveRight,
     RotateClockwise,
     RotateAnticlockwise,
+    Rotate180,
+    HardDrop,
+    Hold,
     EndGame,
 }
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Coord {
     pub col: i16,
     pub row: i16,
 }
 
 pub struct TetrisPiece {
+    piece_shape: PieceShape,
     shape: Vec<Coord>,
     centre: Coord,
+    rotation: RotationState,
+    // Cleared whenever the piece moves without rotating, so T-spins are only
+    // credited for rotating into place.
+    last_rotation: Option<LastRotation>,
+}
+/// How the piece last rotated, for telling T-spins apart.
+#[derive(Debug, Clone, Copy)]
+struct LastRotation {
+    kick_index: usize,
+    half_turn: bool,
 }
 impl TetrisPiece {
-    pub fn new(piece_shape: &PieceShape) -> Self {
+    // The last kick in each quarter turn SRS table, which upgrades a T-spin
+    // mini to full.
+    const FINAL_KICK: usize = 4;
+
+    pub fn new(piece_shape: &PieceShape, board: &TetrisBoard) -> Self {
         Self {
+            piece_shape: piece_shape.clone(),
             shape: piece_shape.shape(),
-            centre: Coord { col: 4, row: 2 },
+            centre: board.spawn_centre(piece_shape),
+            rotation: RotationState::Spawn,
+            last_rotation: None,
         }
     }
+    /// Brings a new piece into play in the buffer and, as the guideline
+    /// has it, drops it a row straight away if there is space. The game is
+    /// blocked out if the piece overlaps the stack where it spawns.
+    pub fn spawn(piece_shape: &PieceShape, board: &TetrisBoard) -> Result<Self, GameOverReason> {
+        let mut piece = Self::new(piece_shape, board);
+        if board.check_is_valid_position(&piece.coordinates()) != PiecePositionValidity::Valid {
+            return Err(GameOverReason::BlockOut);
+        }
+        piece.move_down(board);
+        Ok(piece)
+    }
+    pub fn piece_shape(&self) -> PieceShape {
+        self.piece_shape.clone()
+    }
+    pub fn rotation(&self) -> RotationState {
+        self.rotation
+    }
     pub fn coordinates(&self) -> Vec<Coord> {
         self.calc_coordinates_with_centre(None)
     }
@@ -179,125 +528,292 @@ impl TetrisPiece {
                 row: coords.row + new_centre.row,
             });
         }
-        return coordinates;
+        coordinates
     }
+    /// Moves or rotates the piece, returning `TurnEvent::PieceMoved` if the
+    /// piece ended up somewhere new.
     pub fn move_peice(&mut self, board: &TetrisBoard, direction: Command) -> Option<TurnEvent> {
-        match direction {
-            Command::MoveRight => {
-                let new_centre = Coord {
-                    col: self.centre.col + 1,
-                    row: self.centre.row,
-                };
-                let new_coordinates = self.calc_coordinates_with_centre(Some(&new_centre));
-                if let PiecePositionValidity::Valid =
-                    board.check_is_valid_position(&new_coordinates)
-                {
-                    self.centre.col += 1;
-                }
-                return None;
+        let moved = match direction {
+            Command::MoveRight => self.shift(board, 1),
+            Command::MoveLeft => self.shift(board, -1),
+            Command::MoveDown => self.move_down(board),
+            Command::RotateClockwise | Command::RotateAnticlockwise | Command::Rotate180 => {
+                self.rotate(board, direction)
             }
-            Command::MoveLeft => {
-                let new_centre = Coord {
-                    col: self.centre.col - 1,
-                    row: self.centre.row,
-                };
-                let new_coordinates = self.calc_coordinates_with_centre(Some(&new_centre));
-                if let PiecePositionValidity::Valid =
-                    board.check_is_valid_position(&new_coordinates)
-                {
-                    self.centre.col -= 1;
-                }
-                return None;
-            }
-            Command::MoveDown => Some(TurnEvent::EndTurn),
-            Command::RotateClockwise => {
-                if let Ok(new_coordinates) = self.calc_rotated_shape(Command::RotateClockwise) {
-                    if let PiecePositionValidity::Valid = board.check_is_valid_position(
-                        &new_coordinates
-                            .iter()
-                            .map(|x| Coord {
-                                col: x.col + self.centre.col,
-                                row: x.row + self.centre.row,
-                            })
-                            .collect(),
-                    ) {
-                        self.shape = new_coordinates;
-                    }
-                }
-                return None;
-            }
-            Command::RotateAnticlockwise => {
-                if let Ok(new_coordinates) = self.calc_rotated_shape(Command::RotateAnticlockwise) {
-                    if let PiecePositionValidity::Valid = board.check_is_valid_position(
-                        &new_coordinates
-                            .iter()
-                            .map(|x| Coord {
-                                col: x.col + self.centre.col,
-                                row: x.row + self.centre.row,
-                            })
-                            .collect(),
-                    ) {
-                        self.shape = new_coordinates;
-                    }
-                }
-                return None;
-            }
-            _other => None,
+            _other => false,
+        };
+        if moved {
+            return Some(TurnEvent::PieceMoved);
         }
+        None
     }
-
-    pub fn move_down(mut self, board: &mut TetrisBoard) -> Option<Self> {
+    fn shift(&mut self, board: &TetrisBoard, col_offset: i16) -> bool {
+        let new_centre = Coord {
+            col: self.centre.col + col_offset,
+            row: self.centre.row,
+        };
+        let new_coordinates = self.calc_coordinates_with_centre(Some(&new_centre));
+        if let PiecePositionValidity::Valid = board.check_is_valid_position(&new_coordinates) {
+            self.centre = new_centre;
+            self.last_rotation = None;
+            return true;
+        }
+        false
+    }
+    /// Moves the piece down a single row if there is space below it. This
+    /// never locks the piece, it just reports whether it moved.
+    pub fn move_down(&mut self, board: &TetrisBoard) -> bool {
         let new_centre = Coord {
             row: self.centre.row + 1,
             ..self.centre
         };
-        match board.check_is_valid_position(&self.calc_coordinates_with_centre(Some(&new_centre))) {
-            PiecePositionValidity::Valid => {
-                self.centre = new_centre;
-                return Some(self);
+        if let PiecePositionValidity::Valid =
+            board.check_is_valid_position(&self.calc_coordinates_with_centre(Some(&new_centre)))
+        {
+            self.centre = new_centre;
+            self.last_rotation = None;
+            return true;
+        }
+        false
+    }
+    /// Moves the piece up out of the way of garbage pushed up underneath it,
+    /// as far as it needs to go. The game tops out if there is no room
+    /// left for it in the buffer.
+    pub fn push_up(&mut self, board: &TetrisBoard) -> Result<(), GameOverReason> {
+        let top_row = -(board.buffer_rows() as i16);
+        loop {
+            let coordinates = self.coordinates();
+            if let PiecePositionValidity::Valid = board.check_is_valid_position(&coordinates) {
+                return Ok(());
             }
-            _other => {
-                board.fix_piece_in_place(self);
-                return None;
+            if coordinates.iter().any(|coord| coord.row <= top_row) {
+                return Err(GameOverReason::TopOut);
             }
+            self.centre.row -= 1;
         }
     }
-    fn calc_rotated_shape(&self, direction: Command) -> Result<Vec<Coord>, ()> {
-        match direction {
-            Command::RotateClockwise => {
-                let mut new_coords = vec![];
-                for coord in &self.shape {
-                    new_coords.push(Coord {
-                        col: coord.row,
-                        row: -1 * coord.col,
-                    });
-                }
-                return Ok(new_coords);
+    /// The row of the piece's lowest cell.
+    pub fn bottom_row(&self) -> i16 {
+        self.coordinates()
+            .iter()
+            .map(|coord| coord.row)
+            .max()
+            .unwrap_or(self.centre.row)
+    }
+    /// Whether the piece is resting on the stack or the floor.
+    pub fn is_grounded(&self, board: &TetrisBoard) -> bool {
+        self.drop_distance(board) == 0
+    }
+    /// Fixes the piece to the board where it is. The game is locked out if
+    /// none of the piece made it down into the visible field.
+    pub fn lock(self, board: &mut TetrisBoard) -> Result<(), GameOverReason> {
+        let locked_out = self.coordinates().iter().all(|coord| coord.row < 0);
+        board.fix_piece_in_place(self);
+        match locked_out {
+            true => Err(GameOverReason::LockOut),
+            false => Ok(()),
+        }
+    }
+    /// How many rows the piece can fall before it lands on the stack or the
+    /// floor.
+    pub fn drop_distance(&self, board: &TetrisBoard) -> u16 {
+        let mut distance = 0;
+        loop {
+            let new_centre = Coord {
+                row: self.centre.row + distance + 1,
+                ..self.centre
+            };
+            if board.check_is_valid_position(&self.calc_coordinates_with_centre(Some(&new_centre)))
+                != PiecePositionValidity::Valid
+            {
+                return distance as u16;
             }
-            Command::RotateAnticlockwise => {
-                let mut new_coords = vec![];
-                for coord in &self.shape {
-                    new_coords.push(Coord {
-                        col: -1 * coord.row,
-                        row: coord.col,
-                    });
-                }
-                return Ok(new_coords);
+            distance += 1;
+        }
+    }
+    /// Where the piece would end up if it were dropped straight down, for
+    /// showing a ghost of where it will land.
+    pub fn landing_coordinates(&self, board: &TetrisBoard) -> Vec<Coord> {
+        let landing_centre = Coord {
+            row: self.centre.row + self.drop_distance(board) as i16,
+            ..self.centre
+        };
+        self.calc_coordinates_with_centre(Some(&landing_centre))
+    }
+    /// Drops the piece straight down as far as it will go and locks it in
+    /// place, returning the number of rows it fell.
+    pub fn hard_drop(mut self, board: &mut TetrisBoard) -> Result<u16, GameOverReason> {
+        let rows_dropped = self.drop_distance(board);
+        if rows_dropped > 0 {
+            self.centre.row += rows_dropped as i16;
+            self.last_rotation = None;
+        }
+        self.lock(board)?;
+        Ok(rows_dropped)
+    }
+    /// Rotates the piece using the SRS wall kicks, trying each kick offset
+    /// in turn and leaving the piece where it is if none of them fit.
+    /// Returns whether the rotation succeeded.
+    fn rotate(&mut self, board: &TetrisBoard, direction: Command) -> bool {
+        let new_rotation = match direction {
+            Command::RotateClockwise => self.rotation.clockwise(),
+            Command::RotateAnticlockwise => self.rotation.anticlockwise(),
+            Command::Rotate180 => self.rotation.half_turn(),
+            _other => return false,
+        };
+        let half_turn = matches!(direction, Command::Rotate180);
+        let Ok(new_shape) = self.calc_rotated_shape(direction) else {
+            return false;
+        };
+        for (kick_index, kick) in srs::kicks(&self.piece_shape, self.rotation, new_rotation)
+            .into_iter()
+            .enumerate()
+        {
+            let new_centre = Coord {
+                col: self.centre.col + kick.col,
+                row: self.centre.row + kick.row,
+            };
+            let new_coordinates: Vec<Coord> = new_shape
+                .iter()
+                .map(|coord| Coord {
+                    col: coord.col + new_centre.col,
+                    row: coord.row + new_centre.row,
+                })
+                .collect();
+            if let PiecePositionValidity::Valid = board.check_is_valid_position(&new_coordinates) {
+                self.shape = new_shape;
+                self.centre = new_centre;
+                self.rotation = new_rotation;
+                self.last_rotation = Some(LastRotation {
+                    kick_index,
+                    half_turn,
+                });
+                return true;
             }
-            _other => Err(()),
         }
+        false
+    }
+    /// Checks whether the piece, where it is now, is a T-spin by the
+    /// 3-corner rule. Corners off the board count as filled.
+    pub fn t_spin(&self, board: &TetrisBoard) -> TSpin {
+        let Some(last_rotation) = self.last_rotation else {
+            return TSpin::None;
+        };
+        if self.piece_shape != PieceShape::T {
+            return TSpin::None;
+        }
+        let is_filled = |col_offset: i16, row_offset: i16| {
+            let coordinates = vec![Coord {
+                col: self.centre.col + col_offset,
+                row: self.centre.row + row_offset,
+            }];
+            board.check_is_valid_position(&coordinates) != PiecePositionValidity::Valid
+        };
+        // The corners either side of the point of the T, then the two behind it.
+        let (front, back) = match self.rotation {
+            RotationState::Spawn => ([(-1, -1), (1, -1)], [(-1, 1), (1, 1)]),
+            RotationState::Right => ([(1, -1), (1, 1)], [(-1, -1), (-1, 1)]),
+            RotationState::Reverse => ([(-1, 1), (1, 1)], [(-1, -1), (1, -1)]),
+            RotationState::Left => ([(-1, -1), (-1, 1)], [(1, -1), (1, 1)]),
+        };
+        let count_filled = |corners: [(i16, i16); 2]| {
+            corners
+                .iter()
+                .filter(|(col, row)| is_filled(*col, *row))
+                .count()
+        };
+        let front_filled = count_filled(front);
+        if front_filled + count_filled(back) < 3 {
+            return TSpin::None;
+        }
+        let upgrade_kick = !last_rotation.half_turn && last_rotation.kick_index == Self::FINAL_KICK;
+        if front_filled == 2 || upgrade_kick {
+            return TSpin::Full;
+        }
+        TSpin::Mini
+    }
+    fn calc_rotated_shape(&self, direction: Command) -> Result<Vec<Coord>, ()> {
+        rotate_shape(
+            &self.shape,
+            self.piece_shape.rotates_about_cell_corner(),
+            direction,
+        )
+    }
+}
+/// Turns the cells of a piece a quarter or half turn about its centre.
+pub(crate) fn rotate_shape(
+    shape: &[Coord],
+    corner_pivot: bool,
+    direction: Command,
+) -> Result<Vec<Coord>, ()> {
+    // Pieces that turn about a cell corner are rotated in doubled
+    // coordinates so that the pivot lands on a whole number.
+    let to_doubled = |value: i16| if corner_pivot { 2 * value - 1 } else { value };
+    let from_doubled = |value: i16| if corner_pivot { (value + 1) / 2 } else { value };
+    let rotate: fn(i16, i16) -> (i16, i16) = match direction {
+        // Rows count down the board, so clockwise takes right to down.
+        Command::RotateClockwise => |col, row| (-row, col),
+        Command::RotateAnticlockwise => |col, row| (row, -col),
+        Command::Rotate180 => |col, row| (-col, -row),
+        _other => return Err(()),
+    };
+    Ok(shape
+        .iter()
+        .map(|coord| {
+            let (col, row) = rotate(to_doubled(coord.col), to_doubled(coord.row));
+            Coord {
+                col: from_doubled(col),
+                row: from_doubled(row),
+            }
+        })
+        .collect())
+}
+/// The ways a game can end: lost following the guideline, or won by
+/// reaching the goal of its mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason {
+    /// A new piece spawned overlapping the stack.
+    BlockOut,
+    /// A piece locked without any of it inside the visible field.
+    LockOut,
+    /// Garbage pushed the stack up past the top of the buffer.
+    TopOut,
+    /// The player cleared all the lines the mode asked for.
+    GoalReached,
+    /// The mode's time limit ran out.
+    TimeUp,
+}
+impl fmt::Display for GameOverReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            GameOverReason::BlockOut => "Block out",
+            GameOverReason::LockOut => "Lock out",
+            GameOverReason::TopOut => "Top out",
+            GameOverReason::GoalReached => "Goal reached",
+            GameOverReason::TimeUp => "Time up",
+        };
+        write!(f, "{}", description)
     }
 }
 pub enum TurnEvent {
-    EndTurn,
+    PieceMoved,
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn fill_row(tetris_board: &mut TetrisBoard, row: usize) {
+        for col in 0..tetris_board.num_cols() {
+            tetris_board.set_cell(row, col, Cell::Garbage);
+        }
+    }
     #[test]
     fn test_piece_creation_matches_piece() {
+        let tetris_board = TetrisBoard::new();
         for piece_shape in PieceShape::iterator() {
-            let tetris_piece = TetrisPiece::new(piece_shape);
+            let tetris_piece = TetrisPiece::new(piece_shape, &tetris_board);
 
             for i in 0..piece_shape.shape().len() {
                 assert_eq!(tetris_piece.shape[i].col, piece_shape.shape()[i].col);
@@ -307,9 +823,11 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn test_piece_coordinates_generated() {
+        let tetris_board = TetrisBoard::new();
         for piece_shape in PieceShape::iterator() {
-            let tetris_piece = TetrisPiece::new(piece_shape);
+            let tetris_piece = TetrisPiece::new(piece_shape, &tetris_board);
             let piece_coordinates = tetris_piece.coordinates();
             for i in 0..piece_shape.shape().len() {
                 assert_eq!(
@@ -328,7 +846,7 @@ mod tests {
         let tetris_board = TetrisBoard::new();
         let off_board_coords = vec![
             vec![Coord { row: 0, col: -1 }],
-            vec![Coord { row: -1, col: 0 }],
+            vec![Coord { row: -5, col: 0 }],
             vec![Coord { row: 0, col: 20 }],
             vec![Coord { row: 20, col: 0 }],
         ];
@@ -351,7 +869,7 @@ mod tests {
     #[test]
     fn test_piece_position_validity_returns_collision() {
         let mut tetris_board = TetrisBoard::new();
-        tetris_board.board[0][0] = true;
+        tetris_board.set_cell(0, 0, Cell::Garbage);
         assert_eq!(
             tetris_board.check_is_valid_position(&vec![Coord { row: 0, col: 0 }]),
             PiecePositionValidity::PieceCollision
@@ -361,11 +879,11 @@ mod tests {
     #[test]
     fn test_piece_cannot_go_off_board() {
         let tetris_board = TetrisBoard::new();
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         for _ in 0..20 {
             tetris_piece.move_peice(&tetris_board, Command::MoveRight);
             for coord in tetris_piece.coordinates() {
-                assert!(coord.col < TetrisBoard::NUM_COLS as i16);
+                assert!(coord.col < tetris_board.num_cols() as i16);
             }
         }
         for _ in 0..20 {
@@ -378,69 +896,81 @@ mod tests {
 
     #[test]
     fn test_piece_moves_down_if_no_collision() {
-        let mut tetris_board = TetrisBoard::new();
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         let start_centre = tetris_piece.centre.clone();
         let expected_centre = Coord {
             row: start_centre.row + 1,
             ..start_centre
         };
-        tetris_piece = tetris_piece.move_down(&mut tetris_board).unwrap();
+        assert!(tetris_piece.move_down(&tetris_board));
         assert_eq!(tetris_piece.centre, expected_centre);
     }
 
     #[test]
     fn test_fix_in_place_updates_board() {
         let mut tetris_board = TetrisBoard::new();
-        let tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         let piece_centre = tetris_piece.centre.clone();
         tetris_board.fix_piece_in_place(tetris_piece);
-        assert!(tetris_board.board[piece_centre.row as usize][piece_centre.col as usize])
+        assert!(tetris_board.is_occupied(piece_centre.row as usize, piece_centre.col as usize));
+        assert_eq!(
+            tetris_board.cell(piece_centre.row as usize, piece_centre.col as usize),
+            Cell::Piece(PieceShape::Bar)
+        );
     }
 
     #[test]
-    fn piece_is_fixed_if_down_is_collision() {
+    fn piece_is_grounded_if_down_is_collision() {
         let mut tetris_board = TetrisBoard::new();
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         tetris_piece.centre = Coord { row: 0, col: 2 };
-        tetris_board.board[tetris_piece.centre.row as usize + 1]
-            [tetris_piece.centre.col as usize] = true;
-        tetris_piece.move_down(&mut tetris_board);
+        tetris_board.set_cell(
+            tetris_piece.centre.row as usize + 1,
+            tetris_piece.centre.col as usize,
+            Cell::Garbage,
+        );
+        assert!(!tetris_piece.move_down(&tetris_board));
+        assert!(tetris_piece.is_grounded(&tetris_board));
+        tetris_piece.lock(&mut tetris_board).unwrap();
         for i in 2..4 {
-            assert!(tetris_board.board[0][i as usize]);
+            assert!(tetris_board.is_occupied(0, i));
         }
     }
     #[test]
-    fn piece_is_fixed_if_down_is_end_of_board() {
+    fn piece_is_grounded_if_down_is_end_of_board() {
         let mut tetris_board = TetrisBoard::new();
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         tetris_piece.centre = Coord { row: 15, col: 2 };
-        tetris_piece.move_down(&mut tetris_board);
+        assert!(!tetris_piece.move_down(&tetris_board));
+        assert!(tetris_piece.is_grounded(&tetris_board));
+        tetris_piece.lock(&mut tetris_board).unwrap();
         for i in 2..4 {
-            assert!(tetris_board.board[15][i as usize]);
+            assert!(tetris_board.is_occupied(15, i));
         }
     }
     #[test]
     fn piece_rotates_clockwise() {
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         let expected_coordiantes = vec![
             vec![
-                Coord { col: 0, row: 1 },
-                Coord { col: 0, row: 0 },
-                Coord { col: 0, row: -1 },
-                Coord { col: 0, row: -2 },
-            ],
-            vec![
+                Coord { col: 1, row: -1 },
                 Coord { col: 1, row: 0 },
-                Coord { col: 0, row: 0 },
-                Coord { col: -1, row: 0 },
-                Coord { col: -2, row: 0 },
+                Coord { col: 1, row: 1 },
+                Coord { col: 1, row: 2 },
             ],
             vec![
-                Coord { col: 0, row: -1 },
-                Coord { col: 0, row: 0 },
+                Coord { col: 2, row: 1 },
+                Coord { col: 1, row: 1 },
                 Coord { col: 0, row: 1 },
+                Coord { col: -1, row: 1 },
+            ],
+            vec![
                 Coord { col: 0, row: 2 },
+                Coord { col: 0, row: 1 },
+                Coord { col: 0, row: 0 },
+                Coord { col: 0, row: -1 },
             ],
         ];
         for expected_shape in expected_coordiantes {
@@ -452,25 +982,26 @@ mod tests {
     }
     #[test]
     fn piece_rotates_anticlockwise() {
-        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar);
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
         let expected_coordiantes = vec![
             vec![
-                Coord { col: 0, row: -1 },
-                Coord { col: 0, row: 0 },
-                Coord { col: 0, row: 1 },
                 Coord { col: 0, row: 2 },
-            ],
-            vec![
-                Coord { col: 1, row: 0 },
+                Coord { col: 0, row: 1 },
                 Coord { col: 0, row: 0 },
-                Coord { col: -1, row: 0 },
-                Coord { col: -2, row: 0 },
+                Coord { col: 0, row: -1 },
             ],
             vec![
+                Coord { col: 2, row: 1 },
+                Coord { col: 1, row: 1 },
                 Coord { col: 0, row: 1 },
-                Coord { col: 0, row: 0 },
-                Coord { col: 0, row: -1 },
-                Coord { col: 0, row: -2 },
+                Coord { col: -1, row: 1 },
+            ],
+            vec![
+                Coord { col: 1, row: -1 },
+                Coord { col: 1, row: 0 },
+                Coord { col: 1, row: 1 },
+                Coord { col: 1, row: 2 },
             ],
         ];
         for expected_shape in expected_coordiantes {
@@ -484,18 +1015,625 @@ mod tests {
     #[test]
     fn test_clear_rows_returns_num_full_rows() {
         let mut tetris_board = TetrisBoard::new();
-        tetris_board.board[10] = vec![true; TetrisBoard::NUM_COLS];
-        tetris_board.board[11] = vec![true; TetrisBoard::NUM_COLS];
-        tetris_board.board[12] = vec![true; TetrisBoard::NUM_COLS];
-        assert_eq!(3, tetris_board.clear_rows());
+        for row in 10..13 {
+            fill_row(&mut tetris_board, row);
+        }
+        assert_eq!(3, tetris_board.clear_rows().rows);
     }
 
     #[test]
     fn test_clear_rows_shifts_rows() {
         let mut tetris_board = TetrisBoard::new();
-        tetris_board.board[TetrisBoard::NUM_ROWS - 1] = vec![true; TetrisBoard::NUM_COLS];
-        tetris_board.board[TetrisBoard::NUM_ROWS - 2][0] = true;
+        let last_row = tetris_board.num_rows() - 1;
+        fill_row(&mut tetris_board, last_row);
+        tetris_board.set_cell(last_row - 1, 0, Cell::Garbage);
         tetris_board.clear_rows();
-        assert!(tetris_board.board[TetrisBoard::NUM_ROWS - 1][0]);
+        assert!(tetris_board.is_occupied(last_row, 0));
+    }
+
+    #[test]
+    fn test_board_with_size_sets_dimensions() {
+        let tetris_board = TetrisBoard::with_size(20, 4);
+        assert_eq!(tetris_board.num_rows(), 20);
+        assert_eq!(tetris_board.num_cols(), 4);
+        assert_eq!(tetris_board.rows().len(), 20);
+        assert!(tetris_board.rows().iter().all(|row| *row == 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_board_with_size_rejects_boards_too_small_to_spawn() {
+        TetrisBoard::with_size(20, 3);
+    }
+
+    #[test]
+    fn test_piece_position_validity_uses_board_size() {
+        let tetris_board = TetrisBoard::with_size(20, 16);
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vec![Coord { row: 19, col: 15 }]),
+            PiecePositionValidity::Valid
+        );
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vec![Coord { row: 20, col: 15 }]),
+            PiecePositionValidity::OffOfBoard
+        );
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vec![Coord { row: 19, col: 16 }]),
+            PiecePositionValidity::OffOfBoard
+        );
+    }
+
+    #[test]
+    fn test_pieces_spawn_on_board_of_any_width() {
+        for num_cols in [4, 10, 16] {
+            let tetris_board = TetrisBoard::with_size(20, num_cols);
+            for piece_shape in PieceShape::iterator() {
+                let tetris_piece = TetrisPiece::new(piece_shape, &tetris_board);
+                assert_eq!(
+                    tetris_board.check_is_valid_position(&tetris_piece.coordinates()),
+                    PiecePositionValidity::Valid
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_clear_rows_on_wide_board() {
+        let mut tetris_board = TetrisBoard::with_size(8, 16);
+        fill_row(&mut tetris_board, 7);
+        tetris_board.set_cell(6, 15, Cell::Garbage);
+        assert_eq!(1, tetris_board.clear_rows().rows);
+        assert_eq!(tetris_board.rows().len(), 8);
+        assert!(tetris_board.is_occupied(7, 15));
+    }
+
+    #[test]
+    fn test_full_row_mask_matches_board_width() {
+        for num_cols in [4, 10, 64] {
+            let mut tetris_board = TetrisBoard::with_size(4, num_cols);
+            assert!(!tetris_board.is_row_full(3));
+            fill_row(&mut tetris_board, 3);
+            assert!(tetris_board.is_row_full(3));
+            tetris_board.set_cell(3, num_cols - 1, Cell::Empty);
+            assert!(!tetris_board.is_row_full(3));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_board_with_size_rejects_boards_wider_than_row_mask() {
+        TetrisBoard::with_size(20, 65);
+    }
+
+    #[test]
+    fn test_collision_checks_every_row_of_piece() {
+        let mut tetris_board = TetrisBoard::new();
+        tetris_board.set_cell(5, 3, Cell::Garbage);
+        let vertical_bar = vec![
+            Coord { row: 2, col: 3 },
+            Coord { row: 3, col: 3 },
+            Coord { row: 4, col: 3 },
+            Coord { row: 5, col: 3 },
+        ];
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vertical_bar),
+            PiecePositionValidity::PieceCollision
+        );
+        tetris_board.set_cell(5, 3, Cell::Empty);
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vertical_bar),
+            PiecePositionValidity::Valid
+        );
+    }
+
+    #[test]
+    fn test_clear_rows_keeps_gaps_between_cleared_rows() {
+        let mut tetris_board = TetrisBoard::with_size(6, 4);
+        fill_row(&mut tetris_board, 5);
+        tetris_board.set_cell(4, 1, Cell::Garbage);
+        fill_row(&mut tetris_board, 3);
+        tetris_board.set_cell(2, 2, Cell::Garbage);
+        assert_eq!(2, tetris_board.clear_rows().rows);
+        assert_eq!(tetris_board.rows(), &[0, 0, 0, 0, 1 << 2, 1 << 1]);
+    }
+
+    #[test]
+    fn test_square_rotates_in_place() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Square, &tetris_board);
+        let mut start_coordinates = tetris_piece.coordinates();
+        start_coordinates.sort_by_key(|coord| (coord.row, coord.col));
+        for _ in 0..4 {
+            tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+            let mut coordinates = tetris_piece.coordinates();
+            coordinates.sort_by_key(|coord| (coord.row, coord.col));
+            assert_eq!(coordinates, start_coordinates);
+        }
+    }
+
+    #[test]
+    fn test_rotation_tracks_rotation_state() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        assert_eq!(tetris_piece.rotation(), RotationState::Right);
+        tetris_piece.move_peice(&tetris_board, Command::RotateAnticlockwise);
+        tetris_piece.move_peice(&tetris_board, Command::RotateAnticlockwise);
+        assert_eq!(tetris_piece.rotation(), RotationState::Left);
+    }
+
+    #[test]
+    fn test_t_piece_kicks_off_left_wall() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        for _ in 0..10 {
+            tetris_piece.move_peice(&tetris_board, Command::MoveLeft);
+        }
+        // Pointing right against the wall, the centre is in the first column.
+        assert_eq!(tetris_piece.centre.col, 0);
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        assert_eq!(tetris_piece.rotation(), RotationState::Reverse);
+        assert_eq!(tetris_piece.centre.col, 1);
+    }
+
+    #[test]
+    fn test_bar_kicks_off_right_wall() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        for _ in 0..10 {
+            tetris_piece.move_peice(&tetris_board, Command::MoveRight);
+        }
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        assert_eq!(tetris_piece.rotation(), RotationState::Reverse);
+        for coord in tetris_piece.coordinates() {
+            assert!(coord.col < tetris_board.num_cols() as i16);
+        }
+    }
+
+    #[test]
+    fn test_rotation_fails_when_no_kick_fits() {
+        let mut tetris_board = TetrisBoard::with_size(4, 4);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        tetris_piece.centre.row = 2;
+        for col in 0..4 {
+            tetris_board.set_cell(1, col, Cell::Garbage);
+            tetris_board.set_cell(3, col, Cell::Garbage);
+        }
+        let start_coordinates = tetris_piece.coordinates();
+        tetris_piece.move_peice(&tetris_board, Command::RotateClockwise);
+        assert_eq!(tetris_piece.rotation(), RotationState::Spawn);
+        assert_eq!(tetris_piece.coordinates(), start_coordinates);
+    }
+
+    #[test]
+    fn test_hard_drop_locks_piece_on_floor() {
+        let mut tetris_board = TetrisBoard::new();
+        let tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        let start_row = tetris_piece.centre.row;
+        let rows_dropped = tetris_piece.hard_drop(&mut tetris_board).unwrap();
+        let last_row = tetris_board.num_rows() - 1;
+        assert_eq!(rows_dropped as i16, last_row as i16 - start_row);
+        assert!(!tetris_board.is_row_full(last_row));
+        for col in 3..7 {
+            assert!(tetris_board.is_occupied(last_row, col));
+        }
+    }
+
+    #[test]
+    fn test_hard_drop_lands_on_stack() {
+        let mut tetris_board = TetrisBoard::new();
+        tetris_board.set_cell(10, 4, Cell::Garbage);
+        let tetris_piece = TetrisPiece::new(&PieceShape::Square, &tetris_board);
+        let rows_dropped = tetris_piece.hard_drop(&mut tetris_board).unwrap();
+        // The square spawns with its bottom row just above the visible field.
+        assert_eq!(rows_dropped, 10);
+        assert!(tetris_board.is_occupied(9, 4));
+        assert!(tetris_board.is_occupied(8, 5));
+    }
+
+    #[test]
+    fn test_soft_drop_moves_piece_down_one_row() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        let start_row = tetris_piece.centre.row;
+        tetris_piece.move_peice(&tetris_board, Command::MoveDown);
+        assert_eq!(tetris_piece.centre.row, start_row + 1);
+    }
+
+    #[test]
+    fn test_move_down_does_not_lock_piece() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        let last_row = tetris_board.num_rows() as i16 - 1;
+        tetris_piece.centre.row = last_row;
+        assert!(!tetris_piece.move_down(&tetris_board));
+        assert_eq!(tetris_piece.centre.row, last_row);
+        assert!(tetris_board.rows().iter().all(|row| *row == 0));
+    }
+
+    #[test]
+    fn test_landing_coordinates_sit_on_stack() {
+        let mut tetris_board = TetrisBoard::new();
+        tetris_board.set_cell(12, 4, Cell::Garbage);
+        let tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        let mut landing = tetris_piece.landing_coordinates(&tetris_board);
+        landing.sort_by_key(|coord| (coord.row, coord.col));
+        assert_eq!(
+            landing,
+            vec![
+                Coord { col: 4, row: 10 },
+                Coord { col: 3, row: 11 },
+                Coord { col: 4, row: 11 },
+                Coord { col: 5, row: 11 },
+            ]
+        );
+        // Finding the landing spot leaves the piece where it is.
+        assert_eq!(
+            tetris_piece.centre,
+            tetris_board.spawn_centre(&PieceShape::T)
+        );
+    }
+
+    #[test]
+    fn test_landing_coordinates_of_grounded_piece_are_its_own() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        tetris_piece.centre.row = tetris_board.num_rows() as i16 - 1;
+        assert_eq!(tetris_piece.drop_distance(&tetris_board), 0);
+        assert_eq!(
+            tetris_piece.landing_coordinates(&tetris_board),
+            tetris_piece.coordinates()
+        );
+    }
+
+    #[test]
+    fn test_move_peice_reports_only_successful_moves() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Bar, &tetris_board);
+        for _ in 0..3 {
+            assert!(matches!(
+                tetris_piece.move_peice(&tetris_board, Command::MoveLeft),
+                Some(TurnEvent::PieceMoved)
+            ));
+        }
+        assert!(tetris_piece
+            .move_peice(&tetris_board, Command::MoveLeft)
+            .is_none());
+        assert!(matches!(
+            tetris_piece.move_peice(&tetris_board, Command::RotateClockwise),
+            Some(TurnEvent::PieceMoved)
+        ));
+    }
+
+    // A T-spin double slot in the bottom two rows, with an overhang on the
+    // left, and a T pointing right sat just above it ready to spin in.
+    fn t_spin_double_setup() -> (TetrisBoard, TetrisPiece) {
+        let mut tetris_board = TetrisBoard::new();
+        fill_row(&mut tetris_board, 15);
+        tetris_board.set_cell(15, 4, Cell::Empty);
+        fill_row(&mut tetris_board, 14);
+        for col in 3..6 {
+            tetris_board.set_cell(14, col, Cell::Empty);
+        }
+        tetris_board.set_cell(13, 3, Cell::Garbage);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        tetris_piece.shape = tetris_piece
+            .calc_rotated_shape(Command::RotateClockwise)
+            .unwrap();
+        tetris_piece.rotation = RotationState::Right;
+        tetris_piece.centre = Coord { col: 4, row: 14 };
+        (tetris_board, tetris_piece)
+    }
+
+    // A T flat on the floor against the left wall, with one of the corners
+    // by its point filled.
+    fn t_spin_mini_setup() -> (TetrisBoard, TetrisPiece) {
+        let mut tetris_board = TetrisBoard::new();
+        tetris_board.set_cell(14, 0, Cell::Garbage);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        tetris_piece.centre = Coord { col: 1, row: 15 };
+        tetris_piece.last_rotation = Some(LastRotation {
+            kick_index: 0,
+            half_turn: false,
+        });
+        (tetris_board, tetris_piece)
+    }
+
+    #[test]
+    fn test_t_spin_double_is_reported_on_line_clear() {
+        let (mut tetris_board, mut tetris_piece) = t_spin_double_setup();
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::None);
+        assert!(tetris_piece.rotate(&tetris_board, Command::RotateClockwise));
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::Full);
+        tetris_piece.lock(&mut tetris_board).unwrap();
+        assert_eq!(
+            tetris_board.clear_rows(),
+            LineClear {
+                rows: 2,
+                t_spin: TSpin::Full,
+                perfect_clear: false,
+            }
+        );
+        assert_eq!(tetris_board.clear_rows().t_spin, TSpin::None);
+    }
+
+    #[test]
+    fn test_t_spin_mini_with_one_front_corner() {
+        let (tetris_board, tetris_piece) = t_spin_mini_setup();
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::Mini);
+    }
+
+    #[test]
+    fn test_final_kick_upgrades_t_spin_mini() {
+        let (tetris_board, mut tetris_piece) = t_spin_mini_setup();
+        tetris_piece.last_rotation = Some(LastRotation {
+            kick_index: TetrisPiece::FINAL_KICK,
+            half_turn: false,
+        });
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::Full);
+    }
+
+    #[test]
+    fn test_moving_after_rotating_is_not_a_t_spin() {
+        let (tetris_board, mut tetris_piece) = t_spin_mini_setup();
+        assert!(tetris_piece.shift(&tetris_board, 1));
+        assert!(tetris_piece.shift(&tetris_board, -1));
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::None);
+    }
+
+    #[test]
+    fn test_only_t_pieces_t_spin() {
+        let (tetris_board, mut tetris_piece) = t_spin_mini_setup();
+        tetris_piece.piece_shape = PieceShape::FlippedL;
+        assert_eq!(tetris_piece.t_spin(&tetris_board), TSpin::None);
+    }
+
+    #[test]
+    fn test_clearing_every_row_is_a_perfect_clear() {
+        let mut tetris_board = TetrisBoard::new();
+        fill_row(&mut tetris_board, 14);
+        fill_row(&mut tetris_board, 15);
+        let line_clear = tetris_board.clear_rows();
+        assert_eq!(line_clear.rows, 2);
+        assert!(line_clear.perfect_clear);
+        assert_eq!(line_clear.event(), Some(GameEvent::PerfectClear));
+        assert!(tetris_board.is_empty());
+        // An empty board with nothing cleared is not a perfect clear.
+        assert!(!tetris_board.clear_rows().perfect_clear);
+    }
+
+    #[test]
+    fn test_leftover_cells_are_not_a_perfect_clear() {
+        let mut tetris_board = TetrisBoard::new();
+        fill_row(&mut tetris_board, 15);
+        tetris_board.set_cell(14, 0, Cell::Garbage);
+        let line_clear = tetris_board.clear_rows();
+        assert_eq!(line_clear.rows, 1);
+        assert!(!line_clear.perfect_clear);
+        assert_eq!(line_clear.event(), None);
+    }
+
+    #[test]
+    fn test_clear_rows_moves_cells_with_rows() {
+        let mut tetris_board = TetrisBoard::new();
+        fill_row(&mut tetris_board, 15);
+        tetris_board.set_cell(14, 0, Cell::Piece(PieceShape::T));
+        tetris_board.set_cell(14, 1, Cell::Special);
+        tetris_board.set_cell(13, 0, Cell::Garbage);
+        tetris_board.clear_rows();
+        assert_eq!(tetris_board.cell(15, 0), Cell::Piece(PieceShape::T));
+        assert_eq!(tetris_board.cell(15, 1), Cell::Special);
+        assert_eq!(tetris_board.cell(14, 0), Cell::Garbage);
+        assert_eq!(tetris_board.cell(13, 0), Cell::Empty);
+        assert!(!tetris_board.is_occupied(13, 0));
+        tetris_board.set_cell(15, 1, Cell::Empty);
+        assert!(!tetris_board.is_occupied(15, 1));
+    }
+
+    #[test]
+    fn test_buffer_rows_sit_above_visible_rows() {
+        let tetris_board = TetrisBoard::with_buffer(4, 4, 2);
+        assert_eq!(tetris_board.num_rows(), 4);
+        assert_eq!(tetris_board.buffer_rows(), 2);
+        assert_eq!(tetris_board.rows().len(), 4);
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vec![Coord { row: -2, col: 0 }]),
+            PiecePositionValidity::Valid
+        );
+        assert_eq!(
+            tetris_board.check_is_valid_position(&vec![Coord { row: -3, col: 0 }]),
+            PiecePositionValidity::OffOfBoard
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_board_rejects_buffer_too_short_to_spawn() {
+        TetrisBoard::with_buffer(4, 4, 1);
+    }
+
+    #[test]
+    fn test_pieces_spawn_in_buffer_and_drop_a_row() {
+        let tetris_board = TetrisBoard::new();
+        for piece_shape in PieceShape::iterator() {
+            let tetris_piece = TetrisPiece::new(piece_shape, &tetris_board);
+            let bottom_row = tetris_piece.coordinates().iter().map(|c| c.row).max();
+            assert_eq!(bottom_row, Some(-1));
+            let tetris_piece = TetrisPiece::spawn(piece_shape, &tetris_board).unwrap();
+            let bottom_row = tetris_piece.coordinates().iter().map(|c| c.row).max();
+            assert_eq!(bottom_row, Some(0));
+        }
+    }
+
+    #[test]
+    fn test_spawning_into_stack_is_block_out() {
+        let mut tetris_board = TetrisBoard::with_buffer(4, 4, 2);
+        for row in 0..4 {
+            fill_row(&mut tetris_board, row);
+        }
+        // Spawns, but cannot drop into the visible field.
+        let tetris_piece = TetrisPiece::spawn(&PieceShape::T, &tetris_board).unwrap();
+        tetris_piece.lock(&mut tetris_board).unwrap_err();
+        assert!(matches!(
+            TetrisPiece::spawn(&PieceShape::T, &tetris_board),
+            Err(GameOverReason::BlockOut)
+        ));
+    }
+
+    #[test]
+    fn test_locking_above_visible_field_is_lock_out() {
+        let mut tetris_board = TetrisBoard::with_buffer(4, 4, 2);
+        for row in 0..4 {
+            fill_row(&mut tetris_board, row);
+        }
+        let tetris_piece = TetrisPiece::spawn(&PieceShape::Bar, &tetris_board).unwrap();
+        assert_eq!(
+            tetris_piece.hard_drop(&mut tetris_board),
+            Err(GameOverReason::LockOut)
+        );
+        // A piece reaching down into the visible field is fine.
+        tetris_board.clear_rows();
+        let tetris_piece = TetrisPiece::spawn(&PieceShape::Bar, &tetris_board).unwrap();
+        assert_eq!(tetris_piece.hard_drop(&mut tetris_board), Ok(3));
+    }
+
+    fn hole_cols(tetris_board: &TetrisBoard) -> Vec<usize> {
+        let full_row = RowMask::MAX >> (TetrisBoard::MAX_NUM_COLS - tetris_board.num_cols());
+        tetris_board
+            .rows()
+            .iter()
+            .filter(|row| **row != 0)
+            .map(|row| (full_row & !row).trailing_zeros() as usize)
+            .collect()
+    }
+
+    #[test]
+    fn test_push_garbage_moves_stack_up() {
+        let mut tetris_board = TetrisBoard::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        let last_row = tetris_board.num_rows() - 1;
+        tetris_board.set_cell(last_row, 3, Cell::Piece(PieceShape::T));
+        tetris_board
+            .push_garbage(2, GarbageHoles::SingleColumn, &mut rng)
+            .unwrap();
+        assert_eq!(
+            tetris_board.cell(last_row - 2, 3),
+            Cell::Piece(PieceShape::T)
+        );
+        for row in [last_row - 1, last_row] {
+            let garbage = (0..tetris_board.num_cols())
+                .filter(|col| tetris_board.cell(row, *col) == Cell::Garbage)
+                .count();
+            assert_eq!(garbage, tetris_board.num_cols() - 1);
+        }
+        // Garbage clears like any other row.
+        tetris_board.set_cell(last_row, hole_cols(&tetris_board)[1], Cell::Garbage);
+        assert_eq!(tetris_board.clear_rows().rows, 1);
+    }
+
+    #[test]
+    fn test_garbage_hole_placement() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut tetris_board = TetrisBoard::with_size(16, 10);
+        tetris_board
+            .push_garbage(8, GarbageHoles::SingleColumn, &mut rng)
+            .unwrap();
+        let holes = hole_cols(&tetris_board);
+        assert_eq!(holes.len(), 8);
+        assert!(holes.iter().all(|hole| *hole == holes[0]));
+
+        let mut tetris_board = TetrisBoard::with_size(16, 10);
+        tetris_board
+            .push_garbage(16, GarbageHoles::Random, &mut rng)
+            .unwrap();
+        let holes = hole_cols(&tetris_board);
+        assert!(holes.iter().any(|hole| *hole != holes[0]));
+
+        let mut tetris_board = TetrisBoard::with_size(16, 10);
+        tetris_board
+            .push_garbage(
+                16,
+                GarbageHoles::Messy {
+                    change_percent: 100,
+                },
+                &mut rng,
+            )
+            .unwrap();
+        let holes = hole_cols(&tetris_board);
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+
+        let mut tetris_board = TetrisBoard::with_size(16, 10);
+        tetris_board
+            .push_garbage(16, GarbageHoles::Messy { change_percent: 0 }, &mut rng)
+            .unwrap();
+        let holes = hole_cols(&tetris_board);
+        assert!(holes.iter().all(|hole| *hole == holes[0]));
+    }
+
+    #[test]
+    fn test_push_garbage_past_buffer_tops_out() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut tetris_board = TetrisBoard::with_buffer(4, 4, 2);
+        tetris_board
+            .push_garbage(5, GarbageHoles::Random, &mut rng)
+            .unwrap();
+        assert_eq!(
+            tetris_board.push_garbage(2, GarbageHoles::Random, &mut rng),
+            Err(GameOverReason::TopOut)
+        );
+    }
+
+    #[test]
+    fn test_garbage_pushes_piece_up() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut tetris_board = TetrisBoard::with_buffer(4, 4, 2);
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::Square, &tetris_board);
+        tetris_piece.centre.row = 1;
+        tetris_board
+            .push_garbage(2, GarbageHoles::Random, &mut rng)
+            .unwrap();
+        tetris_piece.push_up(&tetris_board).unwrap();
+        let bottom_row = tetris_piece.coordinates().iter().map(|c| c.row).max();
+        assert_eq!(bottom_row, Some(1));
+        tetris_board
+            .push_garbage(3, GarbageHoles::Random, &mut rng)
+            .unwrap();
+        assert_eq!(
+            tetris_piece.push_up(&tetris_board),
+            Err(GameOverReason::TopOut)
+        );
+    }
+
+    #[test]
+    fn test_rotate_180_matches_two_quarter_turns() {
+        let tetris_board = TetrisBoard::new();
+        for piece_shape in PieceShape::iterator() {
+            let mut half_turned = TetrisPiece::new(piece_shape, &tetris_board);
+            half_turned.centre.row = 8;
+            let mut quarter_turned = TetrisPiece::new(piece_shape, &tetris_board);
+            quarter_turned.centre.row = 8;
+            assert!(half_turned.rotate(&tetris_board, Command::Rotate180));
+            quarter_turned.rotate(&tetris_board, Command::RotateClockwise);
+            quarter_turned.rotate(&tetris_board, Command::RotateClockwise);
+            assert_eq!(half_turned.rotation(), RotationState::Reverse);
+            let mut expected = quarter_turned.coordinates();
+            let mut actual = half_turned.coordinates();
+            expected.sort_by_key(|coord| (coord.row, coord.col));
+            actual.sort_by_key(|coord| (coord.row, coord.col));
+            assert_eq!(actual, expected, "{:?}", piece_shape);
+        }
+    }
+
+    #[test]
+    fn test_rotate_180_kicks_off_floor() {
+        let tetris_board = TetrisBoard::new();
+        let mut tetris_piece = TetrisPiece::new(&PieceShape::T, &tetris_board);
+        tetris_piece.centre.row = tetris_board.num_rows() as i16 - 1;
+        assert!(tetris_piece.rotate(&tetris_board, Command::Rotate180));
+        // The T pointing down cannot fit in place, so takes the first kick up.
+        assert_eq!(tetris_piece.centre.row, tetris_board.num_rows() as i16 - 2);
+        assert!(tetris_piece.last_rotation.unwrap().half_turn);
     }
 }
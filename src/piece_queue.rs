@@ -0,0 +1,105 @@
+use crate::models::PieceShape;
+use crate::randomizer::Randomizer;
+use std::collections::VecDeque;
+
+/// The upcoming pieces, kept topped up from a randomizer so that the player
+/// can always see a fixed number of pieces ahead.
+pub struct PieceQueue {
+    randomizer: Box<dyn Randomizer>,
+    preview: VecDeque<PieceShape>,
+}
+impl PieceQueue {
+    pub const MIN_PREVIEW_LEN: usize = 1;
+    pub const MAX_PREVIEW_LEN: usize = 6;
+
+    /// Panics if `preview_len` is outside `MIN_PREVIEW_LEN..=MAX_PREVIEW_LEN`.
+    pub fn new(mut randomizer: Box<dyn Randomizer>, preview_len: usize) -> Self {
+        assert!(
+            (Self::MIN_PREVIEW_LEN..=Self::MAX_PREVIEW_LEN).contains(&preview_len),
+            "Preview must show between {} and {} pieces, got {}.",
+            Self::MIN_PREVIEW_LEN,
+            Self::MAX_PREVIEW_LEN,
+            preview_len
+        );
+        let preview = (0..preview_len).map(|_| randomizer.next_piece()).collect();
+        Self {
+            randomizer,
+            preview,
+        }
+    }
+    /// Takes the piece at the front of the queue, refilling the back.
+    pub fn next_piece(&mut self) -> PieceShape {
+        self.preview.push_back(self.randomizer.next_piece());
+        self.preview.pop_front().unwrap()
+    }
+    /// The upcoming pieces, the next to be played first.
+    pub fn preview(&self) -> impl Iterator<Item = PieceShape> + '_ {
+        self.preview.iter().cloned()
+    }
+    pub fn preview_len(&self) -> usize {
+        self.preview.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece_set::PieceSet;
+    use crate::randomizer::RandomizerKind;
+
+    struct CyclingRandomizer {
+        count: usize,
+    }
+    impl Randomizer for CyclingRandomizer {
+        fn next_piece(&mut self) -> PieceShape {
+            let piece = PieceShape::iterator().nth(self.count % 7).unwrap().clone();
+            self.count += 1;
+            piece
+        }
+    }
+
+    #[test]
+    fn test_queue_deals_pieces_in_randomizer_order() {
+        let mut queue = PieceQueue::new(Box::new(CyclingRandomizer { count: 0 }), 3);
+        for expected in PieceShape::iterator().chain(PieceShape::iterator()) {
+            assert_eq!(queue.next_piece(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_queue_preview_stays_full() {
+        let mut queue = PieceQueue::new(Box::new(CyclingRandomizer { count: 0 }), 3);
+        assert_eq!(
+            queue.preview().collect::<Vec<PieceShape>>(),
+            vec![PieceShape::Square, PieceShape::Bar, PieceShape::Z]
+        );
+        queue.next_piece();
+        assert_eq!(queue.preview_len(), 3);
+        assert_eq!(
+            queue.preview().collect::<Vec<PieceShape>>(),
+            vec![PieceShape::Bar, PieceShape::Z, PieceShape::FlippedZ]
+        );
+    }
+
+    #[test]
+    fn test_preview_shows_what_is_dealt() {
+        let mut queue =
+            PieceQueue::new(RandomizerKind::SevenBag.build(&PieceSet::standard(), 3), 6);
+        for _ in 0..20 {
+            let upcoming = queue.preview().next().unwrap();
+            assert_eq!(queue.next_piece(), upcoming);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_queue_rejects_preview_too_long() {
+        PieceQueue::new(RandomizerKind::SevenBag.build(&PieceSet::standard(), 3), 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_queue_rejects_empty_preview() {
+        PieceQueue::new(RandomizerKind::SevenBag.build(&PieceSet::standard(), 3), 0);
+    }
+}
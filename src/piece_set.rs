@@ -0,0 +1,427 @@
+//! The pieces a game is played with: the seven tetrominoes, or a set read
+//! from a text file so that other sets can be played without recompiling.
+//!
+//! A piece set file lists its pieces one after another, each a `[name]`
+//! header followed by `key = value` lines:
+//!
+//! ```text
+//! # The V tromino.
+//! [V]
+//! cells = 0,-1 0,0 1,0
+//! centre = cell
+//! colour = green
+//! spawn = 0
+//! ```
+//!
+//! - `cells`: `col,row` offsets from the rotation centre, rows counting down.
+//! - `centre`: `cell` to turn about the middle of cell `0,0`, or `corner` to
+//!   turn about its bottom right corner as the square and bar do. Defaults
+//!   to `cell`.
+//! - `colour`: one of the `PieceColour` names. Defaults to `white`.
+//! - `spawn`: the orientation the piece spawns in, as a turn of the cells
+//!   given: `0`, `R`, `2` or `L`. Defaults to `0`.
+//!
+//! Blank lines and lines starting with `#` are skipped.
+use crate::models::{rotate_shape, Command, Coord, PieceShape, TetrisBoard};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The colours a piece can be drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceColour {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+    Grey,
+    White,
+}
+impl FromStr for PieceColour {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "red" => Ok(PieceColour::Red),
+            "orange" => Ok(PieceColour::Orange),
+            "yellow" => Ok(PieceColour::Yellow),
+            "green" => Ok(PieceColour::Green),
+            "cyan" => Ok(PieceColour::Cyan),
+            "blue" => Ok(PieceColour::Blue),
+            "magenta" => Ok(PieceColour::Magenta),
+            "grey" => Ok(PieceColour::Grey),
+            "white" => Ok(PieceColour::White),
+            other => Err(format!("Unknown colour '{}'.", other)),
+        }
+    }
+}
+
+/// The point a piece turns about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationCentre {
+    /// The middle of cell `0,0`.
+    Cell,
+    /// The bottom right corner of cell `0,0`.
+    Corner,
+}
+
+/// A piece read from a piece set file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PieceDefinition {
+    pub name: String,
+    /// The cells in the spawn orientation, relative to the rotation centre.
+    pub cells: Vec<Coord>,
+    pub rotation_centre: RotationCentre,
+    pub colour: PieceColour,
+}
+
+/// The pieces the randomizer deals from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceSet {
+    // "standard" for the tetrominoes, or the name of the file the set was
+    // read from.
+    name: String,
+    pieces: Vec<PieceShape>,
+}
+impl Default for PieceSet {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+impl PieceSet {
+    pub fn standard() -> Self {
+        Self {
+            name: String::from("standard"),
+            pieces: PieceShape::iterator().cloned().collect(),
+        }
+    }
+    /// Reads a piece set file. See the module docs for the format.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read '{}': {}.", path.display(), error))?;
+        let mut piece_set = Self::parse(&text)?;
+        if let Some(name) = path.file_stem() {
+            piece_set.name = name.to_string_lossy().into_owned();
+        }
+        Ok(piece_set)
+    }
+    /// Reads a set of pieces in the piece set file format.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut builders: Vec<PieceBuilder> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                let name = name.trim();
+                if builders.iter().any(|builder| builder.name == name) {
+                    return Err(format!(
+                        "Line {}: there is already a piece named '{}'.",
+                        line_number, name
+                    ));
+                }
+                builders.push(PieceBuilder::new(name));
+                continue;
+            }
+            let Some(builder) = builders.last_mut() else {
+                return Err(format!(
+                    "Line {}: expected a [name] before the first piece.",
+                    line_number
+                ));
+            };
+            builder
+                .set(line)
+                .map_err(|message| format!("Line {}: {}", line_number, message))?;
+        }
+        if builders.is_empty() {
+            return Err(String::from("The piece set has no pieces."));
+        }
+        let pieces = builders
+            .into_iter()
+            .map(|builder| builder.build())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name: String::from("custom"),
+            pieces: pieces
+                .into_iter()
+                .map(|definition| PieceShape::Custom(Arc::new(definition)))
+                .collect(),
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn pieces(&self) -> &[PieceShape] {
+        &self.pieces
+    }
+    /// Checks that every piece spawns wholly on `board`, naming the first
+    /// that does not.
+    pub fn check_fits(&self, board: &TetrisBoard) -> Result<(), String> {
+        match self
+            .pieces
+            .iter()
+            .find(|piece_shape| !board.fits_spawn_area(piece_shape))
+        {
+            Some(piece_shape) => Err(format!(
+                "The {} piece does not fit in the spawn area of a {}x{} board with {} buffer rows.",
+                PieceName(piece_shape),
+                board.num_cols(),
+                board.num_rows(),
+                board.buffer_rows()
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// Names a piece in messages to the player.
+struct PieceName<'a>(&'a PieceShape);
+impl fmt::Display for PieceName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            PieceShape::Custom(definition) => write!(f, "'{}'", definition.name),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+// Collects the lines of a piece until the whole piece has been read.
+struct PieceBuilder {
+    name: String,
+    cells: Option<Vec<Coord>>,
+    rotation_centre: RotationCentre,
+    colour: PieceColour,
+    spawn_turn: Option<Command>,
+}
+impl PieceBuilder {
+    fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            cells: None,
+            rotation_centre: RotationCentre::Cell,
+            colour: PieceColour::White,
+            spawn_turn: None,
+        }
+    }
+    fn set(&mut self, line: &str) -> Result<(), String> {
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("expected 'key = value', got '{}'.", line));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "cells" => self.cells = Some(Self::parse_cells(value)?),
+            "centre" => {
+                self.rotation_centre = match value {
+                    "cell" => RotationCentre::Cell,
+                    "corner" => RotationCentre::Corner,
+                    other => return Err(format!("unknown rotation centre '{}'.", other)),
+                }
+            }
+            "colour" => self.colour = value.parse()?,
+            "spawn" => {
+                self.spawn_turn = match value {
+                    "0" => None,
+                    "R" => Some(Command::RotateClockwise),
+                    "2" => Some(Command::Rotate180),
+                    "L" => Some(Command::RotateAnticlockwise),
+                    other => return Err(format!("unknown spawn orientation '{}'.", other)),
+                }
+            }
+            other => return Err(format!("unknown key '{}'.", other)),
+        }
+        Ok(())
+    }
+    fn parse_cells(value: &str) -> Result<Vec<Coord>, String> {
+        value
+            .split_whitespace()
+            .map(|cell| {
+                let (col, row) = cell
+                    .split_once(',')
+                    .ok_or_else(|| format!("expected a cell as 'col,row', got '{}'.", cell))?;
+                let coord = match (col.parse(), row.parse()) {
+                    (Ok(col), Ok(row)) => Coord { col, row },
+                    _other => return Err(format!("invalid cell '{}'.", cell)),
+                };
+                // Checked before the piece is measured or turned, so that the
+                // arithmetic on its offsets cannot overflow.
+                let max_offset = TetrisBoard::MAX_PIECE_ROWS as i16;
+                let offsets = -max_offset..=max_offset;
+                if !offsets.contains(&coord.col) || !offsets.contains(&coord.row) {
+                    return Err(format!(
+                        "cell '{}' is more than {} cells from the centre.",
+                        cell, max_offset
+                    ));
+                }
+                Ok(coord)
+            })
+            .collect()
+    }
+    fn build(self) -> Result<PieceDefinition, String> {
+        let Some(mut cells) = self.cells else {
+            return Err(format!("The '{}' piece has no cells.", self.name));
+        };
+        let distinct: HashSet<(i16, i16)> =
+            cells.iter().map(|coord| (coord.col, coord.row)).collect();
+        if cells.is_empty() || distinct.len() != cells.len() {
+            return Err(format!(
+                "The '{}' piece must have at least one cell and no cell twice.",
+                self.name
+            ));
+        }
+        // Whichever way it turns, the piece must fit the row masks that
+        // collisions are checked with.
+        let span =
+            |values: Vec<i16>| values.iter().max().unwrap() - values.iter().min().unwrap() + 1;
+        let cols = span(cells.iter().map(|coord| coord.col).collect());
+        let rows = span(cells.iter().map(|coord| coord.row).collect());
+        if cols.max(rows) as usize > TetrisBoard::MAX_PIECE_ROWS {
+            return Err(format!(
+                "The '{}' piece is more than {} cells across.",
+                self.name,
+                TetrisBoard::MAX_PIECE_ROWS
+            ));
+        }
+        let corner_pivot = self.rotation_centre == RotationCentre::Corner;
+        if let Some(spawn_turn) = self.spawn_turn {
+            cells = rotate_shape(&cells, corner_pivot, spawn_turn)
+                .expect("Spawn orientations are always turns.");
+        }
+        Ok(PieceDefinition {
+            name: self.name,
+            cells,
+            rotation_centre: self.rotation_centre,
+            colour: self.colour,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(piece_shape: &PieceShape) -> &PieceDefinition {
+        match piece_shape {
+            PieceShape::Custom(definition) => definition,
+            other => panic!("Expected a custom piece, got {:?}.", other),
+        }
+    }
+
+    #[test]
+    fn test_parses_pieces() {
+        let piece_set = PieceSet::parse(
+            "# Two trominoes.\n\
+             [I3]\n\
+             cells = -1,0 0,0 1,0\n\
+             colour = cyan\n\
+             \n\
+             [V]\n\
+             cells = 0,-1 0,0 1,0\n\
+             centre = corner\n\
+             spawn = 2\n",
+        )
+        .unwrap();
+        assert_eq!(piece_set.pieces().len(), 2);
+        let bar = custom(&piece_set.pieces()[0]);
+        assert_eq!(bar.name, "I3");
+        assert_eq!(bar.colour, PieceColour::Cyan);
+        assert_eq!(bar.rotation_centre, RotationCentre::Cell);
+        let v = custom(&piece_set.pieces()[1]);
+        assert_eq!(v.colour, PieceColour::White);
+        // Half turned about the corner below and right of cell 0,0.
+        assert_eq!(
+            v.cells,
+            vec![
+                Coord { col: 1, row: 2 },
+                Coord { col: 1, row: 1 },
+                Coord { col: 0, row: 1 }
+            ]
+        );
+        assert!(piece_set.pieces()[1].rotates_about_cell_corner());
+    }
+
+    #[test]
+    fn test_rejects_bad_piece_sets() {
+        for (text, message) in [
+            ("", "no pieces"),
+            ("cells = 0,0", "Line 1"),
+            (
+                "[A]\ncells = 0,0\n[A]\ncells = 0,0",
+                "already a piece named 'A'",
+            ),
+            ("[A]\ncolour = red", "no cells"),
+            ("[A]\ncells = 0,0 0,0", "no cell twice"),
+            ("[A]\ncells = 0,0 8,0", "more than 8 cells across"),
+            ("[A]\ncells = 0;0", "Line 2"),
+            (
+                "[A]\ncells = -30000,0 30000,0",
+                "more than 8 cells from the centre",
+            ),
+            (
+                "[A]\ncells = 30000,0 30001,0",
+                "more than 8 cells from the centre",
+            ),
+            ("[A]\ncells = 0,-32768", "more than 8 cells from the centre"),
+            ("[A]\ncells = 0,0\ncolour = mauve", "Unknown colour 'mauve'"),
+            ("[A]\ncells = 0,0\nspawn = 3", "unknown spawn orientation"),
+            ("[A]\ncells = 0,0\nweight = 2", "unknown key 'weight'"),
+        ] {
+            let error = PieceSet::parse(text).unwrap_err();
+            assert!(error.contains(message), "{}: {}", text, error);
+        }
+    }
+
+    #[test]
+    fn test_checks_pieces_fit_spawn_area() {
+        let piece_set = PieceSet::parse("[I5]\ncells = -2,0 -1,0 0,0 1,0 2,0").unwrap();
+        assert!(piece_set.check_fits(&TetrisBoard::new()).is_ok());
+        let error = piece_set
+            .check_fits(&TetrisBoard::with_size(16, 4))
+            .unwrap_err();
+        assert!(error.contains("'I5'"), "{}", error);
+        let tall = PieceSet::parse("[Tall]\ncells = 0,0 0,1 0,2").unwrap();
+        assert!(tall.check_fits(&TetrisBoard::with_buffer(4, 4, 3)).is_ok());
+        assert!(tall.check_fits(&TetrisBoard::with_buffer(4, 4, 2)).is_err());
+        assert!(PieceSet::standard()
+            .check_fits(&TetrisBoard::with_buffer(4, 4, 2))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_bundled_piece_sets_load() {
+        for (text, count) in [
+            (include_str!("../pieces/trominoes.txt"), 2),
+            (include_str!("../pieces/pentominoes.txt"), 18),
+        ] {
+            let piece_set = PieceSet::parse(text).unwrap();
+            assert_eq!(piece_set.pieces().len(), count);
+            assert!(piece_set.check_fits(&TetrisBoard::new()).is_ok());
+        }
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("pieces/trominoes.txt");
+        assert_eq!(PieceSet::load(&path).unwrap().name(), "trominoes");
+        assert_eq!(PieceSet::standard().name(), "standard");
+    }
+
+    #[test]
+    fn test_definitions_are_freed_with_the_set() {
+        let piece_set = PieceSet::parse("[I3]\ncells = -1,0 0,0 1,0").unwrap();
+        let PieceShape::Custom(definition) = &piece_set.pieces()[0] else {
+            panic!("Expected a custom piece.");
+        };
+        let definition = Arc::downgrade(definition);
+        let copy = piece_set.clone();
+        drop(piece_set);
+        assert!(definition.upgrade().is_some());
+        drop(copy);
+        assert!(definition.upgrade().is_none());
+    }
+}
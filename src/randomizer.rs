@@ -0,0 +1,250 @@
+//! Generators that decide the order pieces are dealt to the player in.
+//!
+//! Every generator is driven by a seeded RNG, so the same seed always deals
+//! the same sequence of pieces.
+use crate::models::PieceShape;
+use crate::piece_set::PieceSet;
+use rand::rngs::StdRng;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::str::FromStr;
+
+pub trait Randomizer {
+    fn next_piece(&mut self) -> PieceShape;
+}
+
+/// Picks every piece uniformly at random, independent of previous pieces.
+pub struct PureRandom {
+    rng: StdRng,
+    pieces: Vec<PieceShape>,
+}
+impl PureRandom {
+    pub fn new(piece_set: &PieceSet, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            pieces: piece_set.pieces().to_vec(),
+        }
+    }
+}
+impl Randomizer for PureRandom {
+    fn next_piece(&mut self) -> PieceShape {
+        self.pieces.iter().choose(&mut self.rng).unwrap().clone()
+    }
+}
+
+/// Deals pieces from a shuffled bag holding a number of copies of every
+/// piece, refilling the bag once it is empty. One copy of the tetrominoes
+/// gives the guideline 7-bag, two copies the looser 14-bag.
+pub struct BagRandomizer {
+    rng: StdRng,
+    copies: usize,
+    pieces: Vec<PieceShape>,
+    bag: Vec<PieceShape>,
+}
+impl BagRandomizer {
+    pub fn new(piece_set: &PieceSet, copies: usize, seed: u64) -> Self {
+        assert!(
+            copies > 0,
+            "A bag must hold at least one copy of each piece."
+        );
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            copies,
+            pieces: piece_set.pieces().to_vec(),
+            bag: Vec::with_capacity(copies * piece_set.pieces().len()),
+        }
+    }
+    pub fn seven_bag(piece_set: &PieceSet, seed: u64) -> Self {
+        Self::new(piece_set, 1, seed)
+    }
+    pub fn fourteen_bag(piece_set: &PieceSet, seed: u64) -> Self {
+        Self::new(piece_set, 2, seed)
+    }
+    fn refill(&mut self) {
+        for _ in 0..self.copies {
+            self.bag.extend(self.pieces.iter().cloned());
+        }
+        self.bag.shuffle(&mut self.rng);
+    }
+}
+impl Randomizer for BagRandomizer {
+    fn next_piece(&mut self) -> PieceShape {
+        if self.bag.is_empty() {
+            self.refill();
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// The generator from the NES game: roll for a piece, and if it repeats the
+/// previous piece (or lands on the extra "reroll" slot) roll once more and
+/// keep whatever comes up.
+pub struct NesRandomizer {
+    rng: StdRng,
+    pieces: Vec<PieceShape>,
+    previous: Option<PieceShape>,
+}
+impl NesRandomizer {
+    pub fn new(piece_set: &PieceSet, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            pieces: piece_set.pieces().to_vec(),
+            previous: None,
+        }
+    }
+}
+impl Randomizer for NesRandomizer {
+    fn next_piece(&mut self) -> PieceShape {
+        let num_pieces = self.pieces.len();
+        let roll = self.rng.gen_range(0..=num_pieces);
+        let mut piece = self.pieces.get(roll).cloned();
+        if piece.is_none() || piece == self.previous {
+            piece = Some(self.pieces[self.rng.gen_range(0..num_pieces)].clone());
+        }
+        self.previous = piece.clone();
+        piece.unwrap()
+    }
+}
+
+/// The randomizers that can be chosen when starting a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomizerKind {
+    PureRandom,
+    SevenBag,
+    FourteenBag,
+    Nes,
+}
+impl RandomizerKind {
+    pub fn build(&self, piece_set: &PieceSet, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::PureRandom => Box::new(PureRandom::new(piece_set, seed)),
+            RandomizerKind::SevenBag => Box::new(BagRandomizer::seven_bag(piece_set, seed)),
+            RandomizerKind::FourteenBag => Box::new(BagRandomizer::fourteen_bag(piece_set, seed)),
+            RandomizerKind::Nes => Box::new(NesRandomizer::new(piece_set, seed)),
+        }
+    }
+}
+impl FromStr for RandomizerKind {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "random" => Ok(RandomizerKind::PureRandom),
+            "7bag" => Ok(RandomizerKind::SevenBag),
+            "14bag" => Ok(RandomizerKind::FourteenBag),
+            "nes" => Ok(RandomizerKind::Nes),
+            other => Err(format!("Unknown randomizer '{}'.", other)),
+        }
+    }
+}
+impl fmt::Display for RandomizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RandomizerKind::PureRandom => "random",
+            RandomizerKind::SevenBag => "7bag",
+            RandomizerKind::FourteenBag => "14bag",
+            RandomizerKind::Nes => "nes",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_each_piece(pieces: &[PieceShape]) -> Vec<usize> {
+        PieceShape::iterator()
+            .map(|shape| pieces.iter().filter(|piece| *piece == shape).count())
+            .collect()
+    }
+
+    #[test]
+    fn test_seven_bag_deals_every_piece_once_per_bag() {
+        let mut randomizer = BagRandomizer::seven_bag(&PieceSet::standard(), 0);
+        for _ in 0..10 {
+            let bag: Vec<PieceShape> = (0..7).map(|_| randomizer.next_piece()).collect();
+            assert_eq!(count_each_piece(&bag), vec![1; 7]);
+        }
+    }
+
+    #[test]
+    fn test_fourteen_bag_deals_every_piece_twice_per_bag() {
+        let mut randomizer = BagRandomizer::fourteen_bag(&PieceSet::standard(), 0);
+        for _ in 0..10 {
+            let bag: Vec<PieceShape> = (0..14).map(|_| randomizer.next_piece()).collect();
+            assert_eq!(count_each_piece(&bag), vec![2; 7]);
+        }
+    }
+
+    #[test]
+    fn test_pure_random_deals_every_piece_eventually() {
+        let mut randomizer = PureRandom::new(&PieceSet::standard(), 0);
+        let pieces: Vec<PieceShape> = (0..1000).map(|_| randomizer.next_piece()).collect();
+        assert!(count_each_piece(&pieces).iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn test_nes_randomizer_rarely_repeats() {
+        let mut randomizer = NesRandomizer::new(&PieceSet::standard(), 0);
+        let pieces: Vec<PieceShape> = (0..7000).map(|_| randomizer.next_piece()).collect();
+        let repeats = pieces.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        // A repeat needs a reroll, 1 in 4 rolls, to land on the same piece, so
+        // about 1 in 28 pieces against 1 in 7 for a uniform generator.
+        assert!(repeats < 400, "{} repeats", repeats);
+        assert!(count_each_piece(&pieces).iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn test_bag_deals_from_custom_piece_set() {
+        let piece_set = PieceSet::parse("[A]\ncells = 0,0\n[B]\ncells = 0,0 1,0").unwrap();
+        let mut randomizer = BagRandomizer::seven_bag(&piece_set, 0);
+        for _ in 0..10 {
+            let mut bag = vec![randomizer.next_piece(), randomizer.next_piece()];
+            bag.dedup();
+            assert_eq!(bag.len(), 2);
+            assert!(bag.iter().all(|piece| piece_set.pieces().contains(piece)));
+        }
+    }
+
+    const ALL_KINDS: [RandomizerKind; 4] = [
+        RandomizerKind::PureRandom,
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::Nes,
+    ];
+
+    #[test]
+    fn test_same_seed_deals_same_pieces() {
+        for kind in ALL_KINDS {
+            let mut first = kind.build(&PieceSet::standard(), 42);
+            let mut second = kind.build(&PieceSet::standard(), 42);
+            for _ in 0..100 {
+                assert_eq!(first.next_piece(), second.next_piece());
+            }
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_deal_different_pieces() {
+        for kind in ALL_KINDS {
+            let first: Vec<PieceShape> = {
+                let mut randomizer = kind.build(&PieceSet::standard(), 1);
+                (0..100).map(|_| randomizer.next_piece()).collect()
+            };
+            let second: Vec<PieceShape> = {
+                let mut randomizer = kind.build(&PieceSet::standard(), 2);
+                (0..100).map(|_| randomizer.next_piece()).collect()
+            };
+            assert_ne!(first, second);
+        }
+    }
+
+    #[test]
+    fn test_randomizer_kind_round_trips_through_strings() {
+        for kind in ALL_KINDS {
+            assert_eq!(kind.to_string().parse::<RandomizerKind>(), Ok(kind));
+        }
+        assert!("8bag".parse::<RandomizerKind>().is_err());
+    }
+}
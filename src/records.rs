@@ -0,0 +1,211 @@
+//! The best times set in each timed mode, kept in a text file between games.
+//!
+//! Times are kept in lists named by `list_name`, so that only runs played
+//! with the same setup compete. The file has a line per time, the list name
+//! followed by the time in milliseconds:
+//!
+//! ```text
+//! sprint-40,10x16,standard,7bag,preview-5,classic,lock-30,entry-0 83467
+//! ```
+use crate::game::GameConfig;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Names the list a run's time goes in: its mode, and everything about the
+/// setup that changes how quickly it can be played. Runs on a smaller
+/// board or with easier pieces don't compete with standard ones.
+pub fn list_name(config: &GameConfig) -> String {
+    format!(
+        "{},{}x{},{},{},preview-{},{},lock-{},entry-{}",
+        config.mode,
+        config.cols,
+        config.rows,
+        config.piece_set.name(),
+        config.randomizer,
+        config.preview_len,
+        config.level_curve,
+        config.lock_delay,
+        config.entry_delay
+    )
+}
+
+/// The fastest times in each list, quickest first.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BestTimes {
+    times: BTreeMap<String, Vec<Duration>>,
+}
+impl BestTimes {
+    /// How many times are kept in each list.
+    pub const MAX_PER_LIST: usize = 10;
+
+    /// Reads the best times file, or starts an empty list if there is none
+    /// yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("Could not read '{}': {}.", path.display(), error)),
+        }
+    }
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_string())
+            .map_err(|error| format!("Could not write '{}': {}.", path.display(), error))
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut best_times = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            // Piece set names can have spaces, but times can't.
+            let millis = line
+                .rsplit_once(' ')
+                .and_then(|(list, millis)| Some((list, millis.parse().ok()?)));
+            let Some((list, millis)) = millis else {
+                return Err(format!(
+                    "Line {}: expected '<list> <milliseconds>', got '{}'.",
+                    index + 1,
+                    line
+                ));
+            };
+            best_times.record(list.trim(), Duration::from_millis(millis));
+        }
+        Ok(best_times)
+    }
+    /// The best times in `list`, quickest first.
+    pub fn times(&self, list: &str) -> &[Duration] {
+        self.times.get(list).map_or(&[], |times| times.as_slice())
+    }
+    /// Adds a time to `list`, returning its place in the list counting from
+    /// 1, or `None` if it was too slow to make the list. Ties go after the
+    /// times already set, which got there first.
+    pub fn record(&mut self, list: &str, time: Duration) -> Option<usize> {
+        let times = self.times.entry(String::from(list)).or_default();
+        let index = times.partition_point(|best| *best <= time);
+        if index >= Self::MAX_PER_LIST {
+            return None;
+        }
+        times.insert(index, time);
+        times.truncate(Self::MAX_PER_LIST);
+        Some(index + 1)
+    }
+}
+/// Writes the times in the best times file format.
+impl fmt::Display for BestTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (list, times) in &self.times {
+            for time in times {
+                writeln!(f, "{} {}", list, time.as_millis())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_mode::GameMode;
+    use crate::piece_set::PieceSet;
+
+    const SPRINT: &str = "sprint-40,10x16,standard,7bag,preview-5,classic,lock-30,entry-0";
+
+    #[test]
+    fn test_list_name_describes_the_setup() {
+        let sprint = GameConfig {
+            mode: GameMode::Sprint { lines: 40 },
+            ..GameConfig::default()
+        };
+        assert_eq!(list_name(&sprint), SPRINT);
+        let trominoes = PieceSet::parse(include_str!("../pieces/trominoes.txt")).unwrap();
+        for other_setup in [
+            GameConfig {
+                cols: 4,
+                rows: 4,
+                ..sprint.clone()
+            },
+            GameConfig {
+                piece_set: trominoes,
+                ..sprint.clone()
+            },
+            GameConfig {
+                preview_len: 6,
+                ..sprint.clone()
+            },
+            GameConfig {
+                mode: GameMode::Sprint { lines: 20 },
+                ..sprint.clone()
+            },
+        ] {
+            assert_ne!(list_name(&other_setup), SPRINT);
+        }
+        // The seed and the player's handling don't change the list.
+        let handling = GameConfig {
+            seed: 7,
+            das: 6,
+            arr: 0,
+            ..sprint.clone()
+        };
+        assert_eq!(list_name(&handling), SPRINT);
+    }
+
+    #[test]
+    fn test_records_times_in_order() {
+        let mut best_times = BestTimes::default();
+        assert_eq!(best_times.times(SPRINT), &[]);
+        assert_eq!(best_times.record(SPRINT, Duration::from_secs(90)), Some(1));
+        assert_eq!(best_times.record(SPRINT, Duration::from_secs(80)), Some(1));
+        assert_eq!(best_times.record(SPRINT, Duration::from_secs(90)), Some(3));
+        assert_eq!(
+            best_times.times(SPRINT),
+            &[80, 90, 90].map(Duration::from_secs)
+        );
+        // Each list is kept apart.
+        assert_eq!(best_times.times("sprint-20"), &[]);
+    }
+
+    #[test]
+    fn test_keeps_only_the_fastest_times() {
+        let mut best_times = BestTimes::default();
+        for secs in 1..=BestTimes::MAX_PER_LIST as u64 {
+            best_times.record(SPRINT, Duration::from_secs(secs));
+        }
+        assert_eq!(best_times.record(SPRINT, Duration::from_secs(60)), None);
+        assert_eq!(
+            best_times.record(SPRINT, Duration::from_millis(500)),
+            Some(1)
+        );
+        let times = best_times.times(SPRINT);
+        assert_eq!(times.len(), BestTimes::MAX_PER_LIST);
+        assert_eq!(times.last(), Some(&Duration::from_secs(9)));
+    }
+
+    #[test]
+    fn test_writes_and_reads_back_file() {
+        let mut best_times = BestTimes::default();
+        best_times.record(SPRINT, Duration::from_millis(83467));
+        best_times.record("sprint-20,my pieces", Duration::from_millis(41016));
+        let text = best_times.to_string();
+        assert_eq!(
+            text,
+            format!("sprint-20,my pieces 41016\n{} 83467\n", SPRINT)
+        );
+        assert_eq!(BestTimes::parse(&text), Ok(best_times));
+    }
+
+    #[test]
+    fn test_rejects_malformed_lines() {
+        assert!(BestTimes::parse("sprint-40").is_err());
+        assert_eq!(
+            BestTimes::parse("\nsprint-40 fast"),
+            Err(String::from(
+                "Line 2: expected '<list> <milliseconds>', got 'sprint-40 fast'."
+            ))
+        );
+    }
+}
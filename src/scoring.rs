@@ -0,0 +1,355 @@
+//! Points awarded for clearing rows, and the streaks that add to them.
+use crate::models::{LineClear, TSpin};
+use std::fmt;
+use std::str::FromStr;
+
+/// Decides how many points each lock and drop is worth, so that game modes
+/// can score the same play differently. `level` counts from 0.
+pub trait ScoringRule {
+    /// Points for locking a piece, given what it cleared and the streak
+    /// bonuses recorded for it.
+    fn lock_points(&self, line_clear: &LineClear, bonus: &StreakBonus, level: u64) -> u64;
+    fn soft_drop_points(&self, rows: u64) -> u64 {
+        rows
+    }
+    fn hard_drop_points(&self, rows: u64) -> u64 {
+        2 * rows
+    }
+}
+
+/// The NES scoring: 40, 100, 300 or 1200 points times the level for one to
+/// four rows, with no bonuses for spins, streaks or perfect clears.
+pub struct NesClassic;
+impl ScoringRule for NesClassic {
+    fn lock_points(&self, line_clear: &LineClear, _bonus: &StreakBonus, level: u64) -> u64 {
+        let base_points = match line_clear.rows {
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            4 => 1200,
+            _other => 0,
+        };
+        base_points * (level + 1)
+    }
+}
+
+/// Modern guideline scoring, with T-spins, back-to-back and combo bonuses
+/// and perfect clears.
+pub struct Guideline;
+impl Guideline {
+    const COMBO_POINTS: u64 = 50;
+
+    // T-spins score even when they clear nothing.
+    fn clear_points(line_clear: &LineClear) -> u64 {
+        match (line_clear.t_spin, line_clear.rows) {
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, 4) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, 2) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, 3) => 1600,
+            _other => 0,
+        }
+    }
+    // Awarded on top of the points for the rows themselves. A Tetris perfect
+    // clear made back-to-back is worth more.
+    fn perfect_clear_points(line_clear: &LineClear, back_to_back: bool) -> u64 {
+        if !line_clear.perfect_clear {
+            return 0;
+        }
+        match (line_clear.rows, back_to_back) {
+            (1, _) => 800,
+            (2, _) => 1200,
+            (3, _) => 1800,
+            (4, false) => 2000,
+            (4, true) => 3200,
+            _other => 0,
+        }
+    }
+}
+impl ScoringRule for Guideline {
+    fn lock_points(&self, line_clear: &LineClear, bonus: &StreakBonus, level: u64) -> u64 {
+        let clear_points = match bonus.back_to_back {
+            true => Self::clear_points(line_clear) * 3 / 2,
+            false => Self::clear_points(line_clear),
+        };
+        let points = clear_points
+            + Self::COMBO_POINTS * bonus.combo as u64
+            + Self::perfect_clear_points(line_clear, bonus.back_to_back);
+        points * (level + 1)
+    }
+}
+
+/// Scores a point per row cleared and nothing else.
+pub struct LinesOnly;
+impl ScoringRule for LinesOnly {
+    fn lock_points(&self, line_clear: &LineClear, _bonus: &StreakBonus, _level: u64) -> u64 {
+        line_clear.rows as u64
+    }
+    fn soft_drop_points(&self, _rows: u64) -> u64 {
+        0
+    }
+    fn hard_drop_points(&self, _rows: u64) -> u64 {
+        0
+    }
+}
+
+/// The scoring rules that can be chosen when starting a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoringRuleKind {
+    NesClassic,
+    Guideline,
+    LinesOnly,
+}
+impl ScoringRuleKind {
+    pub fn build(&self) -> Box<dyn ScoringRule> {
+        match self {
+            ScoringRuleKind::NesClassic => Box::new(NesClassic),
+            ScoringRuleKind::Guideline => Box::new(Guideline),
+            ScoringRuleKind::LinesOnly => Box::new(LinesOnly),
+        }
+    }
+}
+impl FromStr for ScoringRuleKind {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "nes" => Ok(ScoringRuleKind::NesClassic),
+            "guideline" => Ok(ScoringRuleKind::Guideline),
+            "lines" => Ok(ScoringRuleKind::LinesOnly),
+            other => Err(format!("Unknown scoring rule '{}'.", other)),
+        }
+    }
+}
+impl fmt::Display for ScoringRuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScoringRuleKind::NesClassic => "nes",
+            ScoringRuleKind::Guideline => "guideline",
+            ScoringRuleKind::LinesOnly => "lines",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Whether a clear is hard enough to chain back-to-back: a Tetris, or a
+/// T-spin of any kind that clears rows.
+pub fn is_difficult(line_clear: &LineClear) -> bool {
+    line_clear.rows == 4 || (line_clear.rows > 0 && line_clear.t_spin != TSpin::None)
+}
+
+/// Tracks the combo, the run of consecutive locks that clear rows, and
+/// whether the last clear was a difficult one that the next can chain from.
+pub struct ClearStreak {
+    clearing_locks: u32,
+    back_to_back: bool,
+}
+impl Default for ClearStreak {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The bonuses earned by a single lock.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StreakBonus {
+    pub combo: u32,
+    pub back_to_back: bool,
+}
+
+impl ClearStreak {
+    pub fn new() -> Self {
+        Self {
+            clearing_locks: 0,
+            back_to_back: false,
+        }
+    }
+    /// How many clears in a row have followed the first, 0 when there is no
+    /// combo going.
+    pub fn combo(&self) -> u32 {
+        self.clearing_locks.saturating_sub(1)
+    }
+    /// Whether the next difficult clear will score back-to-back.
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+    /// Updates the streaks with the result of a lock and returns the bonuses
+    /// it earned. Locks that clear nothing end the combo but keep a
+    /// back-to-back chain alive; only an easy clear breaks that.
+    pub fn record(&mut self, line_clear: &LineClear) -> StreakBonus {
+        if line_clear.rows == 0 {
+            self.clearing_locks = 0;
+            return StreakBonus {
+                combo: 0,
+                back_to_back: false,
+            };
+        }
+        self.clearing_locks += 1;
+        let difficult = is_difficult(line_clear);
+        let bonus = StreakBonus {
+            combo: self.combo(),
+            back_to_back: difficult && self.back_to_back,
+        };
+        self.back_to_back = difficult;
+        bonus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(rows: u16, t_spin: TSpin) -> LineClear {
+        LineClear {
+            rows,
+            t_spin,
+            perfect_clear: false,
+        }
+    }
+
+    const NO_BONUS: StreakBonus = StreakBonus {
+        combo: 0,
+        back_to_back: false,
+    };
+
+    #[test]
+    fn test_nes_classic_scores_rows_by_level() {
+        assert_eq!(
+            NesClassic.lock_points(&clear(1, TSpin::None), &NO_BONUS, 0),
+            40
+        );
+        assert_eq!(
+            NesClassic.lock_points(&clear(4, TSpin::None), &NO_BONUS, 1),
+            2400
+        );
+        assert_eq!(
+            NesClassic.lock_points(&clear(0, TSpin::Full), &NO_BONUS, 0),
+            0
+        );
+        let bonus = StreakBonus {
+            combo: 3,
+            back_to_back: true,
+        };
+        assert_eq!(
+            NesClassic.lock_points(&clear(2, TSpin::Full), &bonus, 0),
+            100
+        );
+    }
+
+    #[test]
+    fn test_guideline_t_spins_score_without_clearing() {
+        assert_eq!(
+            Guideline.lock_points(&clear(0, TSpin::None), &NO_BONUS, 0),
+            0
+        );
+        assert_eq!(
+            Guideline.lock_points(&clear(0, TSpin::Mini), &NO_BONUS, 0),
+            100
+        );
+        assert_eq!(
+            Guideline.lock_points(&clear(0, TSpin::Full), &NO_BONUS, 2),
+            1200
+        );
+        assert_eq!(
+            Guideline.lock_points(&clear(2, TSpin::Full), &NO_BONUS, 0),
+            1200
+        );
+        assert_eq!(
+            Guideline.lock_points(&clear(4, TSpin::None), &NO_BONUS, 1),
+            1600
+        );
+    }
+
+    #[test]
+    fn test_guideline_streak_bonuses() {
+        let bonus = StreakBonus {
+            combo: 2,
+            back_to_back: true,
+        };
+        assert_eq!(
+            Guideline.lock_points(&clear(4, TSpin::None), &bonus, 0),
+            1300
+        );
+        let bonus = StreakBonus {
+            combo: 3,
+            back_to_back: false,
+        };
+        assert_eq!(
+            Guideline.lock_points(&clear(2, TSpin::None), &bonus, 1),
+            900
+        );
+    }
+
+    #[test]
+    fn test_guideline_perfect_clear_bonus() {
+        let perfect_clear = |rows| LineClear {
+            perfect_clear: true,
+            ..clear(rows, TSpin::None)
+        };
+        let back_to_back = StreakBonus {
+            combo: 0,
+            back_to_back: true,
+        };
+        assert_eq!(Guideline.lock_points(&perfect_clear(1), &NO_BONUS, 0), 900);
+        assert_eq!(Guideline.lock_points(&perfect_clear(3), &NO_BONUS, 1), 4600);
+        assert_eq!(Guideline.lock_points(&perfect_clear(4), &NO_BONUS, 0), 2800);
+        assert_eq!(
+            Guideline.lock_points(&perfect_clear(4), &back_to_back, 0),
+            4400
+        );
+    }
+
+    #[test]
+    fn test_lines_only_counts_rows() {
+        let bonus = StreakBonus {
+            combo: 3,
+            back_to_back: true,
+        };
+        assert_eq!(LinesOnly.lock_points(&clear(4, TSpin::None), &bonus, 5), 4);
+        assert_eq!(LinesOnly.lock_points(&clear(0, TSpin::Full), &bonus, 5), 0);
+        assert_eq!(LinesOnly.hard_drop_points(10), 0);
+    }
+
+    #[test]
+    fn test_scoring_rule_kind_round_trips_through_strings() {
+        for kind in [
+            ScoringRuleKind::NesClassic,
+            ScoringRuleKind::Guideline,
+            ScoringRuleKind::LinesOnly,
+        ] {
+            assert_eq!(kind.to_string().parse::<ScoringRuleKind>(), Ok(kind));
+        }
+        assert!("tgm".parse::<ScoringRuleKind>().is_err());
+    }
+
+    #[test]
+    fn test_combo_counts_consecutive_clears() {
+        let mut streak = ClearStreak::new();
+        assert_eq!(streak.record(&clear(1, TSpin::None)).combo, 0);
+        assert_eq!(streak.record(&clear(2, TSpin::None)).combo, 1);
+        assert_eq!(streak.record(&clear(1, TSpin::None)).combo, 2);
+        assert_eq!(streak.combo(), 2);
+        assert_eq!(streak.record(&clear(0, TSpin::None)).combo, 0);
+        assert_eq!(streak.combo(), 0);
+        assert_eq!(streak.record(&clear(1, TSpin::None)).combo, 0);
+    }
+
+    #[test]
+    fn test_back_to_back_chains_difficult_clears() {
+        let mut streak = ClearStreak::new();
+        assert!(!streak.record(&clear(4, TSpin::None)).back_to_back);
+        assert!(streak.back_to_back());
+        // Placing a piece without clearing does not break the chain.
+        streak.record(&clear(0, TSpin::None));
+        assert!(streak.record(&clear(2, TSpin::Full)).back_to_back);
+        assert!(streak.record(&clear(1, TSpin::Mini)).back_to_back);
+        assert!(!streak.record(&clear(3, TSpin::None)).back_to_back);
+        assert!(!streak.back_to_back());
+        assert!(!streak.record(&clear(4, TSpin::None)).back_to_back);
+    }
+}
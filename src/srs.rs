@@ -0,0 +1,165 @@
+//! Rotation states and wall kick tables for the Super Rotation System (SRS).
+//!
+//! The kick tables are written as they appear in the Tetris guideline, as
+//! `(x, y)` offsets with `y` pointing up. They are converted to board
+//! coordinates, where rows count down, when they are looked up.
+use crate::models::{Coord, PieceShape};
+
+/// The four orientations a piece can be in, named 0, R, 2 and L in the
+/// guideline. `Spawn` is the orientation pieces enter the board in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationState {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+impl RotationState {
+    pub fn clockwise(self) -> Self {
+        match self {
+            RotationState::Spawn => RotationState::Right,
+            RotationState::Right => RotationState::Reverse,
+            RotationState::Reverse => RotationState::Left,
+            RotationState::Left => RotationState::Spawn,
+        }
+    }
+    pub fn half_turn(self) -> Self {
+        self.clockwise().clockwise()
+    }
+    pub fn anticlockwise(self) -> Self {
+        match self {
+            RotationState::Spawn => RotationState::Left,
+            RotationState::Left => RotationState::Reverse,
+            RotationState::Reverse => RotationState::Right,
+            RotationState::Right => RotationState::Spawn,
+        }
+    }
+}
+
+// The offsets to try when rotating between two states.
+type KickRow<const N: usize> = (RotationState, RotationState, [(i16, i16); N]);
+type KickTable = [KickRow<5>; 8];
+type HalfTurnKickTable = [KickRow<6>; 4];
+
+#[rustfmt::skip]
+const JLSTZ_KICKS: KickTable = [
+    (RotationState::Spawn, RotationState::Right, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    (RotationState::Right, RotationState::Spawn, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    (RotationState::Right, RotationState::Reverse, [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    (RotationState::Reverse, RotationState::Right, [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    (RotationState::Reverse, RotationState::Left, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+    (RotationState::Left, RotationState::Reverse, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    (RotationState::Left, RotationState::Spawn, [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    (RotationState::Spawn, RotationState::Left, [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+];
+
+#[rustfmt::skip]
+const I_KICKS: KickTable = [
+    (RotationState::Spawn, RotationState::Right, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    (RotationState::Right, RotationState::Spawn, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    (RotationState::Right, RotationState::Reverse, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+    (RotationState::Reverse, RotationState::Right, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    (RotationState::Reverse, RotationState::Left, [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    (RotationState::Left, RotationState::Reverse, [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    (RotationState::Left, RotationState::Spawn, [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    (RotationState::Spawn, RotationState::Left, [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+];
+
+// SRS has no 180 degree rotations, so these follow the widely used SRS+
+// table, which every piece shares.
+#[rustfmt::skip]
+const HALF_TURN_KICKS: HalfTurnKickTable = [
+    (RotationState::Spawn, RotationState::Reverse, [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]),
+    (RotationState::Reverse, RotationState::Spawn, [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)]),
+    (RotationState::Right, RotationState::Left, [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)]),
+    (RotationState::Left, RotationState::Right, [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)]),
+];
+
+/// The offsets to try, in order, when rotating a piece from one state to
+/// another. The first offset that leaves the piece in a valid position wins.
+/// Pieces from a custom set kick as J, L, S, T and Z do.
+pub fn kicks(piece_shape: &PieceShape, from: RotationState, to: RotationState) -> Vec<Coord> {
+    let offsets = match piece_shape {
+        // The square never changes shape when rotated, so never needs to kick.
+        PieceShape::Square => None,
+        _other if from.half_turn() == to => find_offsets(&HALF_TURN_KICKS, from, to),
+        PieceShape::Bar => find_offsets(&I_KICKS, from, to),
+        _other => find_offsets(&JLSTZ_KICKS, from, to),
+    };
+    offsets
+        .map(|offsets| {
+            offsets
+                .iter()
+                .map(|(x, y)| Coord { col: *x, row: -y })
+                .collect()
+        })
+        .unwrap_or_else(|| vec![Coord { col: 0, row: 0 }])
+}
+fn find_offsets<const N: usize>(
+    table: &[KickRow<N>],
+    from: RotationState,
+    to: RotationState,
+) -> Option<&[(i16, i16)]> {
+    table
+        .iter()
+        .find(|(table_from, table_to, _)| *table_from == from && *table_to == to)
+        .map(|(_, _, offsets)| offsets.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_states_cycle() {
+        let mut state = RotationState::Spawn;
+        for _ in 0..4 {
+            assert_eq!(state.clockwise().anticlockwise(), state);
+            state = state.clockwise();
+        }
+        assert_eq!(state, RotationState::Spawn);
+    }
+
+    #[test]
+    fn test_kicks_flip_y_axis_onto_rows() {
+        let kicks = kicks(&PieceShape::T, RotationState::Spawn, RotationState::Right);
+        assert_eq!(kicks.len(), 5);
+        assert_eq!(kicks[0], Coord { col: 0, row: 0 });
+        assert_eq!(kicks[2], Coord { col: -1, row: -1 });
+        assert_eq!(kicks[3], Coord { col: 0, row: 2 });
+    }
+
+    #[test]
+    fn test_half_turns_use_their_own_kicks() {
+        for piece_shape in [PieceShape::T, PieceShape::Bar] {
+            let kicks = kicks(&piece_shape, RotationState::Right, RotationState::Left);
+            assert_eq!(kicks.len(), 6);
+            assert_eq!(kicks[2], Coord { col: 1, row: -2 });
+        }
+        assert_eq!(
+            kicks(
+                &PieceShape::Square,
+                RotationState::Spawn,
+                RotationState::Reverse
+            ),
+            vec![Coord { col: 0, row: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_kick_tables_are_reversible() {
+        // Rotating back must try the opposite of every offset tried going forwards.
+        for piece_shape in [PieceShape::T, PieceShape::Bar] {
+            let mut state = RotationState::Spawn;
+            for _ in 0..4 {
+                let forwards = kicks(&piece_shape, state, state.clockwise());
+                let backwards = kicks(&piece_shape, state.clockwise(), state);
+                for (forward, backward) in forwards.iter().zip(backwards) {
+                    assert_eq!(forward.col, -backward.col);
+                    assert_eq!(forward.row, -backward.row);
+                }
+                state = state.clockwise();
+            }
+        }
+    }
+}
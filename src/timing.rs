@@ -0,0 +1,73 @@
+//! The game runs in fixed frames of a sixtieth of a second, and the delays
+//! in its rules are counted in frames.
+pub mod auto_shift;
+pub mod lock_delay;
+
+use std::time::Duration;
+
+pub const FRAMES_PER_SECOND: u64 = 60;
+/// How long a single frame lasts.
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+
+/// The whole number of frames closest to `millis` milliseconds, or `None`
+/// if that is too many frames to count.
+pub fn frames_from_millis(millis: u64) -> Option<u32> {
+    let frames = millis.checked_mul(FRAMES_PER_SECOND)?.checked_add(500)? / 1000;
+    u32::try_from(frames).ok()
+}
+
+/// How long `frames` frames last.
+pub fn duration_from_frames(frames: u64) -> Duration {
+    Duration::from_nanos(frames * 1_000_000_000 / FRAMES_PER_SECOND)
+}
+
+/// Shows a time to the millisecond, as minutes and seconds: `1:23.456`.
+pub fn format_clock(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_from_millis_rounds_to_nearest_frame() {
+        assert_eq!(frames_from_millis(0), Some(0));
+        assert_eq!(frames_from_millis(500), Some(30));
+        assert_eq!(frames_from_millis(1000), Some(60));
+        assert_eq!(frames_from_millis(167), Some(10));
+        assert_eq!(frames_from_millis(8), Some(0));
+        assert_eq!(frames_from_millis(9), Some(1));
+    }
+
+    #[test]
+    fn test_frames_from_millis_rejects_too_many_frames() {
+        // The longest delay that still fits in a u32 of frames.
+        let max_millis = (u32::MAX as u64 * 1000 + 499) / FRAMES_PER_SECOND;
+        assert_eq!(frames_from_millis(max_millis), Some(u32::MAX));
+        assert_eq!(frames_from_millis(max_millis + 1), None);
+        assert_eq!(frames_from_millis(u64::MAX / FRAMES_PER_SECOND), None);
+        assert_eq!(frames_from_millis(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_duration_from_frames() {
+        assert_eq!(duration_from_frames(0), Duration::ZERO);
+        assert_eq!(duration_from_frames(1), FRAME_DURATION);
+        assert_eq!(duration_from_frames(60), Duration::from_secs(1));
+        assert_eq!(duration_from_frames(90), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_format_clock() {
+        assert_eq!(format_clock(Duration::ZERO), "0:00.000");
+        assert_eq!(format_clock(Duration::from_millis(83_456)), "1:23.456");
+        assert_eq!(format_clock(Duration::from_millis(600_007)), "10:00.007");
+    }
+}
@@ -0,0 +1,136 @@
+//! Delayed auto shift (DAS): holding left or right moves the piece once,
+//! then after a delay keeps moving it at the auto repeat rate (ARR).
+use crate::models::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftDirection {
+    Left,
+    Right,
+}
+impl ShiftDirection {
+    /// The direction a command shifts the piece in, if it is a shift.
+    pub fn for_command(command: &Command) -> Option<Self> {
+        match command {
+            Command::MoveLeft => Some(ShiftDirection::Left),
+            Command::MoveRight => Some(ShiftDirection::Right),
+            _other => None,
+        }
+    }
+    pub fn command(&self) -> Command {
+        match self {
+            ShiftDirection::Left => Command::MoveLeft,
+            ShiftDirection::Right => Command::MoveRight,
+        }
+    }
+}
+
+/// Counts the frames a direction has been held and says when the piece
+/// should shift again.
+pub struct AutoShift {
+    delay: u32,
+    repeat_rate: u32,
+    held: Option<ShiftDirection>,
+    frames_held: u32,
+}
+impl Default for AutoShift {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_DELAY, Self::DEFAULT_REPEAT_RATE)
+    }
+}
+
+impl AutoShift {
+    pub const DEFAULT_DELAY: u32 = 10;
+    pub const DEFAULT_REPEAT_RATE: u32 = 2;
+
+    /// `delay` is the frames a direction is held before it starts repeating
+    /// and `repeat_rate` the frames between repeats. A repeat rate of 0
+    /// shifts the piece as far as it can go as soon as the delay is up.
+    pub fn new(delay: u32, repeat_rate: u32) -> Self {
+        Self {
+            delay,
+            repeat_rate,
+            held: None,
+            frames_held: 0,
+        }
+    }
+    pub fn held(&self) -> Option<ShiftDirection> {
+        self.held
+    }
+    /// Starts holding `direction`. The direction pressed last wins, and
+    /// charges from the start.
+    pub fn press(&mut self, direction: ShiftDirection) {
+        self.held = Some(direction);
+        self.frames_held = 0;
+    }
+    /// Stops holding `direction`, if it is the one held.
+    pub fn release(&mut self, direction: ShiftDirection) {
+        if self.held == Some(direction) {
+            self.held = None;
+        }
+    }
+    /// Counts another frame held and returns how many cells the piece
+    /// should shift this frame, `u32::MAX` meaning as far as it can.
+    pub fn tick(&mut self) -> u32 {
+        if self.held.is_none() {
+            return 0;
+        }
+        self.frames_held = self.frames_held.saturating_add(1);
+        if self.frames_held < self.delay {
+            return 0;
+        }
+        if self.repeat_rate == 0 {
+            return u32::MAX;
+        }
+        match (self.frames_held - self.delay) % self.repeat_rate {
+            0 => 1,
+            _other => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shifts_per_frame(auto_shift: &mut AutoShift, frames: usize) -> Vec<u32> {
+        (0..frames).map(|_| auto_shift.tick()).collect()
+    }
+
+    #[test]
+    fn test_shifts_repeat_after_the_delay() {
+        let mut auto_shift = AutoShift::new(3, 2);
+        assert_eq!(shifts_per_frame(&mut auto_shift, 2), vec![0, 0]);
+        auto_shift.press(ShiftDirection::Left);
+        assert_eq!(
+            shifts_per_frame(&mut auto_shift, 7),
+            vec![0, 0, 1, 0, 1, 0, 1]
+        );
+        auto_shift.release(ShiftDirection::Left);
+        assert_eq!(auto_shift.held(), None);
+        assert_eq!(shifts_per_frame(&mut auto_shift, 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_zero_repeat_rate_shifts_all_the_way() {
+        let mut auto_shift = AutoShift::new(2, 0);
+        auto_shift.press(ShiftDirection::Right);
+        assert_eq!(
+            shifts_per_frame(&mut auto_shift, 3),
+            vec![0, u32::MAX, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn test_last_direction_pressed_wins() {
+        let mut auto_shift = AutoShift::default();
+        auto_shift.press(ShiftDirection::Left);
+        shifts_per_frame(&mut auto_shift, 5);
+        auto_shift.press(ShiftDirection::Right);
+        // Letting go of the direction no longer held changes nothing.
+        auto_shift.release(ShiftDirection::Left);
+        assert_eq!(auto_shift.held(), Some(ShiftDirection::Right));
+        let shifts = shifts_per_frame(&mut auto_shift, AutoShift::DEFAULT_DELAY as usize);
+        assert_eq!(shifts.iter().sum::<u32>(), 1);
+        assert_eq!(shifts.last(), Some(&1));
+    }
+}
@@ -0,0 +1,105 @@
+/// How long a piece can rest on the stack before it locks, and how many
+/// times moving or rotating it can restart that wait.
+///
+/// The game counts the frames a piece has been resting itself; this only
+/// keeps count of the resets, and of the lowest row the piece has reached,
+/// so a piece cannot be kept alive forever by spinning it in place.
+pub struct LockDelay {
+    frames: u32,
+    max_resets: u32,
+    resets: u32,
+    lowest_row: Option<i16>,
+}
+impl Default for LockDelay {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_FRAMES, Self::DEFAULT_MAX_RESETS)
+    }
+}
+
+impl LockDelay {
+    pub const DEFAULT_FRAMES: u32 = 30;
+    pub const DEFAULT_MAX_RESETS: u32 = 15;
+
+    pub fn new(frames: u32, max_resets: u32) -> Self {
+        Self {
+            frames,
+            max_resets,
+            resets: 0,
+            lowest_row: None,
+        }
+    }
+    /// Starts the count over for a new piece.
+    pub fn restart(&mut self) {
+        self.resets = 0;
+        self.lowest_row = None;
+    }
+    /// The lock delay in frames.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+    pub fn resets_remaining(&self) -> u32 {
+        self.max_resets - self.resets
+    }
+    /// Records a successful move or rotation of a grounded piece, returning
+    /// whether the lock delay should start over. Once the resets are used up
+    /// the count carries on and the piece locks when it runs out.
+    pub fn try_reset(&mut self) -> bool {
+        if self.resets >= self.max_resets {
+            return false;
+        }
+        self.resets += 1;
+        true
+    }
+    /// Records the piece's bottom reaching `row`, returning whether that is
+    /// lower than it has been before. Only a new lowest row starts the lock
+    /// delay over and gives the piece its resets back; falling back to a row
+    /// it was kicked up from does neither.
+    pub fn reach_row(&mut self, row: i16) -> bool {
+        if self.lowest_row.is_some_and(|lowest_row| row <= lowest_row) {
+            return false;
+        }
+        self.lowest_row = Some(row);
+        self.resets = 0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_delay_defaults() {
+        let lock_delay = LockDelay::default();
+        assert_eq!(lock_delay.frames(), 30);
+        assert_eq!(lock_delay.resets_remaining(), 15);
+    }
+
+    #[test]
+    fn test_lock_delay_resets_are_capped() {
+        let mut lock_delay = LockDelay::new(30, 3);
+        for remaining in (0..3).rev() {
+            assert!(lock_delay.try_reset());
+            assert_eq!(lock_delay.resets_remaining(), remaining);
+        }
+        assert!(!lock_delay.try_reset());
+        assert_eq!(lock_delay.resets_remaining(), 0);
+    }
+
+    #[test]
+    fn test_only_a_new_lowest_row_gives_resets_back() {
+        let mut lock_delay = LockDelay::new(30, 3);
+        assert!(lock_delay.reach_row(5));
+        lock_delay.try_reset();
+        // Kicked up a row and falling back again.
+        assert!(!lock_delay.reach_row(4));
+        assert!(!lock_delay.reach_row(5));
+        assert_eq!(lock_delay.resets_remaining(), 2);
+        assert!(lock_delay.reach_row(6));
+        assert_eq!(lock_delay.resets_remaining(), 3);
+        lock_delay.try_reset();
+        lock_delay.restart();
+        assert_eq!(lock_delay.resets_remaining(), 3);
+        assert!(lock_delay.reach_row(0));
+    }
+}
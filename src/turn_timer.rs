@@ -1,2 +0,0 @@
-pub mod observer;
-pub mod turn_timer;
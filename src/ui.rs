@@ -1,11 +1,16 @@
-use crossterm::event::{poll, read, Event, KeyCode};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use crossterm::event::{
+    poll, read, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::thread::Scope;
 use std::time::Duration;
 
-use crate::models::{Command, TurnEvent};
-use crate::turn_timer::turn_timer::{TimerStatus, TurnTimerSubscriberTrait};
+use crate::models::Command;
 // Struct that runs enable_raw_mode on start and disables when it is
 // dropped so that it is only active in the scope of the instantiation
 struct ScopedRawMode;
@@ -23,106 +28,157 @@ impl Drop for ScopedRawMode {
             .expect("Failed to disable raw mode. Restart terminal to resume normal behaviour.");
     }
 }
-// TODO: Move the run_user_input_loop fn into a class that implements an interface so
-// we don't have to pass in all of these dependencies to this fn.
-pub fn timed_user_input<'a, T: CommandCollector, U: TurnTimerSubscriberTrait + Send + 'a>(
-    mut turn_timer_subscriber: U,
-    command_dispatcher: mpsc::Sender<Command>,
-    turn_event_reciever: mpsc::Receiver<TurnEvent>,
+/// Starts a thread that reads the player's keys until `stop` is set and
+/// sends them through `input_dispatcher` to the game loop. The terminal is
+/// in raw mode for as long as the thread runs.
+pub fn user_input<'a, T: CommandCollector>(
+    input_dispatcher: mpsc::Sender<KeyInput>,
+    stop: &'a AtomicBool,
+    key_bindings: KeyBindings,
     s: &'a Scope<'a, '_>,
 ) {
-    // set up thread for getting cli input
-
     s.spawn(move || {
         let _guard = ScopedRawMode::new();
-        let command_collector = T::new();
-        run_user_input_loop::<T, U>(
-            &mut turn_timer_subscriber,
-            command_dispatcher,
-            command_collector,
-            turn_event_reciever,
-        )
+        let command_collector = T::new(key_bindings);
+        run_user_input_loop::<T>(input_dispatcher, command_collector, stop)
     });
 }
 
-/// Runs a loop to collect commands from the user while the turn timer
-/// is not yet complete. This has been implemented with dependency injection
-/// through the use of generics in order to make testing easier.
-///
-/// Args:
-/// turn_timer_subscriber: a mutable reference to an object that
-/// implements the TurnTimerSubscriberTrait and the deived trait Send
-/// (so that it can be sent into a thread).
-/// command_dispatcher: an mpsc::Sender of type Command, which
-/// is used to send the read commands back to the main thread.
-/// command_collector: an object that implements the CommandCollector trait. This
-/// reference is mutable to make testing easier.
+/// Runs a loop to collect keys from the user until `stop` is set. This has
+/// been implemented with dependency injection through the use of generics
+/// in order to make testing easier.
 ///
 /// Edge cases:
-/// - Timer never completes: unhandled - user must interrupt program
-/// - Get command fails when reading from input
-/// - Command is not recognised
-/// - Send to main fails
-fn run_user_input_loop<'a, T: CommandCollector, U: TurnTimerSubscriberTrait + Send + 'a>(
-    turn_timer_subscriber: &mut U,
-    command_dispatcher: mpsc::Sender<Command>,
+/// - Get input fails when reading from input: the loop ends
+/// - Key is not recognised: ignored
+/// - Send to main fails: the loop ends
+fn run_user_input_loop<T: CommandCollector>(
+    input_dispatcher: mpsc::Sender<KeyInput>,
     mut command_collector: T,
-    turn_event_reciever: mpsc::Receiver<TurnEvent>,
+    stop: &AtomicBool,
 ) {
-    loop {
-        if let Ok(TurnEvent::EndTurn) = turn_event_reciever.try_recv() {
-            return;
-        }
-        match turn_timer_subscriber.get_timer_status() {
-            TimerStatus::TimerComplete => {
-                return;
-            }
-            TimerStatus::TimerNotComplete => match command_collector.get_command() {
-                Ok(val) => match val {
-                    Some(command) => {
-                        if let Err(error) = command_dispatcher.send(command) {
-                            log::warn!("{:?}", error.to_string());
-                            return;
-                        }
-                    }
-                    None => (),
-                },
-                Err(e) => {
-                    log::warn!("Error encountered reading command {:?}", e);
+    while !stop.load(Ordering::Relaxed) {
+        match command_collector.get_input() {
+            Ok(Some(key_input)) => {
+                if let Err(error) = input_dispatcher.send(key_input) {
+                    log::warn!("{:?}", error.to_string());
                     return;
                 }
-            },
+            }
+            Ok(None) => (),
+            Err(e) => {
+                log::warn!("Error encountered reading command {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// A key the player pressed, held or let go of, as the command it is bound
+/// to. Terminals that cannot report key releases send every key as a tap,
+/// with the terminal's own key repeat standing in for holding it.
+#[derive(Debug)]
+pub enum KeyInput {
+    Pressed(Command),
+    Repeated(Command),
+    Released(Command),
+    Tapped(Command),
+}
+
+/// The letter keys the player presses for each command. The arrows, space
+/// and Esc are fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub rotate_clockwise: char,
+    pub rotate_anticlockwise: char,
+    pub rotate_180: char,
+    pub hold: char,
+}
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            rotate_clockwise: 'x',
+            rotate_anticlockwise: 'z',
+            rotate_180: 'a',
+            hold: 'c',
+        }
+    }
+}
+impl KeyBindings {
+    pub fn command_for(&self, key: char) -> Option<Command> {
+        match key {
+            key if key == self.rotate_clockwise => Some(Command::RotateClockwise),
+            key if key == self.rotate_anticlockwise => Some(Command::RotateAnticlockwise),
+            key if key == self.rotate_180 => Some(Command::Rotate180),
+            key if key == self.hold => Some(Command::Hold),
+            ' ' => Some(Command::HardDrop),
+            _other => None,
         }
     }
 }
 
 pub trait CommandCollector {
-    fn new() -> Self;
-    fn get_command(&mut self) -> std::io::Result<Option<Command>>;
+    fn new(key_bindings: KeyBindings) -> Self;
+    fn get_input(&mut self) -> std::io::Result<Option<KeyInput>>;
 }
 
-pub struct CliCommandCollector {}
+pub struct CliCommandCollector {
+    key_bindings: KeyBindings,
+    // Whether the terminal reports key repeats and releases, which it only
+    // does once asked to with the keyboard enhancement flags.
+    reports_releases: bool,
+}
 impl CommandCollector for CliCommandCollector {
-    fn new() -> Self {
-        Self {}
+    fn new(key_bindings: KeyBindings) -> Self {
+        let reports_releases = matches!(supports_keyboard_enhancement(), Ok(true))
+            && execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )
+            .is_ok();
+        Self {
+            key_bindings,
+            reports_releases,
+        }
     }
-    fn get_command(&mut self) -> std::io::Result<Option<Command>> {
+    fn get_input(&mut self) -> std::io::Result<Option<KeyInput>> {
         if poll(Duration::from_millis(2)).expect("Poll of CLI buffer failed.") {
             return match read()? {
-                Event::Key(key_event) => match key_event.code {
-                    KeyCode::Down => Ok(Some(Command::MoveDown)),
-                    KeyCode::Left => Ok(Some(Command::MoveLeft)),
-                    KeyCode::Right => Ok(Some(Command::MoveRight)),
-                    KeyCode::Char('z') => Ok(Some(Command::RotateAnticlockwise)),
-                    KeyCode::Char('x') => Ok(Some(Command::RotateClockwise)),
-                    KeyCode::Esc => Ok(Some(Command::EndGame)),
+                Event::Key(key_event) => {
+                    let command = match key_event.code {
+                        KeyCode::Down => Some(Command::MoveDown),
+                        KeyCode::Left => Some(Command::MoveLeft),
+                        KeyCode::Right => Some(Command::MoveRight),
+                        KeyCode::Char(key) => self.key_bindings.command_for(key),
+                        KeyCode::Esc => Some(Command::EndGame),
 
-                    _other => Ok(None),
-                },
-                _other => panic!("Unrecognised command!"),
+                        _other => None,
+                    };
+                    Ok(
+                        command.map(|command| match (self.reports_releases, key_event.kind) {
+                            (false, _) => KeyInput::Tapped(command),
+                            (true, KeyEventKind::Press) => KeyInput::Pressed(command),
+                            (true, KeyEventKind::Repeat) => KeyInput::Repeated(command),
+                            (true, KeyEventKind::Release) => KeyInput::Released(command),
+                        }),
+                    )
+                }
+                // Resizes, focus changes and the mouse have nothing to do
+                // with the game.
+                _other => Ok(None),
             };
         }
-        return Ok(None);
+        Ok(None)
+    }
+}
+impl Drop for CliCommandCollector {
+    fn drop(&mut self) {
+        if self.reports_releases && execute!(io::stdout(), PopKeyboardEnhancementFlags).is_err() {
+            log::warn!("Failed to restore the terminal's keyboard reporting.");
+        }
     }
 }
 #[cfg(test)]
@@ -140,23 +196,14 @@ mod tests {
         }
         assert!(!is_raw_mode_enabled().unwrap());
     }
-    struct TestTurnTimerSubscriber {
-        outputs: Vec<TimerStatus>,
-    }
-    impl TurnTimerSubscriberTrait for TestTurnTimerSubscriber {
-        fn get_timer_status(&mut self) -> TimerStatus {
-            self.outputs.pop().unwrap_or(TimerStatus::TimerComplete)
-        }
-    }
-
     struct TestCommandCollector {
-        outputs: Vec<std::io::Result<Option<Command>>>,
+        outputs: Vec<std::io::Result<Option<KeyInput>>>,
     }
     impl CommandCollector for TestCommandCollector {
-        fn new() -> Self {
+        fn new(_key_bindings: KeyBindings) -> Self {
             Self { outputs: vec![] }
         }
-        fn get_command(&mut self) -> std::io::Result<Option<Command>> {
+        fn get_input(&mut self) -> std::io::Result<Option<KeyInput>> {
             match self.outputs.pop() {
                 Some(val) => val,
                 None => Ok(None),
@@ -165,80 +212,74 @@ mod tests {
     }
     #[test]
     fn test_loop_does_exit_on_invalid_input() {
-        let mut test_turn_timer = TestTurnTimerSubscriber {
-            outputs: vec![
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-            ],
-        };
-        let (command_dispatcher, _command_reciever) = mpsc::channel();
-        let (_turn_event_sender, turn_event_reciever) = mpsc::channel::<TurnEvent>();
-        let mut command_collector = TestCommandCollector::new();
-        command_collector.outputs.push(Ok(Some(Command::MoveDown)));
+        let stop = AtomicBool::new(false);
+        let (input_dispatcher, input_reciever) = mpsc::channel();
+        let mut command_collector = TestCommandCollector::new(KeyBindings::default());
+        command_collector
+            .outputs
+            .push(Ok(Some(KeyInput::Tapped(Command::MoveLeft))));
         command_collector
             .outputs
             .push(Err(std::io::Error::new(std::io::ErrorKind::NotFound, "")));
 
-        run_user_input_loop::<TestCommandCollector, TestTurnTimerSubscriber>(
-            &mut test_turn_timer,
-            command_dispatcher,
-            command_collector,
-            turn_event_reciever,
-        );
-        assert_eq!(
-            test_turn_timer.get_timer_status(),
-            TimerStatus::TimerNotComplete
-        );
+        run_user_input_loop::<TestCommandCollector>(input_dispatcher, command_collector, &stop);
+        assert!(input_reciever.try_recv().is_err());
     }
     #[test]
-    fn test_loop_exits_on_end_turn_event() {
-        let mut test_turn_timer = TestTurnTimerSubscriber {
-            outputs: vec![
-                TimerStatus::TimerComplete,
-                TimerStatus::TimerComplete,
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-            ],
-        };
-        let (command_dispatcher, _command_reciever) = mpsc::channel();
-        let (turn_event_sender, turn_event_reciever) = mpsc::channel::<TurnEvent>();
-        let mut command_collector = TestCommandCollector::new();
-        command_collector.outputs.push(Ok(Some(Command::MoveDown)));
-        turn_event_sender
-            .send(TurnEvent::EndTurn)
-            .expect("Sent end turn event to closed channel.");
-        run_user_input_loop::<TestCommandCollector, TestTurnTimerSubscriber>(
-            &mut test_turn_timer,
-            command_dispatcher,
-            command_collector,
-            turn_event_reciever,
-        );
-        assert_eq!(test_turn_timer.outputs.len(), 5);
+    fn test_loop_exits_when_stopped() {
+        let stop = AtomicBool::new(true);
+        let (input_dispatcher, input_reciever) = mpsc::channel();
+        let mut command_collector = TestCommandCollector::new(KeyBindings::default());
+        command_collector
+            .outputs
+            .push(Ok(Some(KeyInput::Tapped(Command::MoveDown))));
+        run_user_input_loop::<TestCommandCollector>(input_dispatcher, command_collector, &stop);
+        assert!(input_reciever.try_recv().is_err());
     }
     #[test]
     fn test_loop_does_not_exit_on_valid_input() {
-        let mut test_turn_timer = TestTurnTimerSubscriber {
-            outputs: vec![
-                TimerStatus::TimerComplete,
-                TimerStatus::TimerComplete,
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-                TimerStatus::TimerNotComplete,
-            ],
-        };
-        let (command_dispatcher, _command_reciever) = mpsc::channel();
-        let (_turn_event_sender, turn_event_reciever) = mpsc::channel::<TurnEvent>();
-        let mut command_collector = TestCommandCollector::new();
-        command_collector.outputs.push(Ok(Some(Command::MoveDown)));
+        let stop = AtomicBool::new(false);
+        let (input_dispatcher, input_reciever) = mpsc::channel();
+        let mut command_collector = TestCommandCollector::new(KeyBindings::default());
+        command_collector
+            .outputs
+            .push(Err(std::io::Error::new(std::io::ErrorKind::NotFound, "")));
+        command_collector
+            .outputs
+            .push(Ok(Some(KeyInput::Released(Command::MoveLeft))));
+        command_collector.outputs.push(Ok(None));
+        command_collector
+            .outputs
+            .push(Ok(Some(KeyInput::Pressed(Command::MoveLeft))));
 
-        run_user_input_loop::<TestCommandCollector, TestTurnTimerSubscriber>(
-            &mut test_turn_timer,
-            command_dispatcher,
-            command_collector,
-            turn_event_reciever,
-        );
-        assert_eq!(test_turn_timer.outputs.len(), 1);
+        run_user_input_loop::<TestCommandCollector>(input_dispatcher, command_collector, &stop);
+        assert!(matches!(
+            input_reciever.try_recv(),
+            Ok(KeyInput::Pressed(Command::MoveLeft))
+        ));
+        assert!(matches!(
+            input_reciever.try_recv(),
+            Ok(KeyInput::Released(Command::MoveLeft))
+        ));
+    }
+    #[test]
+    fn test_key_bindings_map_keys_to_commands() {
+        let key_bindings = KeyBindings {
+            rotate_180: 'q',
+            ..KeyBindings::default()
+        };
+        assert!(matches!(
+            key_bindings.command_for('q'),
+            Some(Command::Rotate180)
+        ));
+        assert!(matches!(
+            key_bindings.command_for('x'),
+            Some(Command::RotateClockwise)
+        ));
+        assert!(matches!(
+            key_bindings.command_for(' '),
+            Some(Command::HardDrop)
+        ));
+        assert!(key_bindings.command_for('a').is_none());
     }
 }
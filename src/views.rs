@@ -1,17 +1,33 @@
-use crate::models::{Coord, TetrisBoard, TetrisPiece};
+use crate::models::{Cell, Coord, GameEvent, PieceShape, TetrisBoard, TetrisPiece};
+use crate::piece_set::{PieceColour, PieceSet};
+use crate::scoring::ClearStreak;
+use crate::timing::format_clock;
+use crate::ui::KeyBindings;
+use crossterm::style::Stylize;
 use crossterm::terminal;
 use crossterm::{cursor, execute, queue, style};
 use std::io;
 use std::io::Write;
+use std::time::Duration;
 
 pub struct CliView<W: Write> {
     writer: W,
+    // Column that the intro and score text is drawn from, to the right of the board.
+    panel_col: u16,
+    // The box the hold and next pieces are drawn in, big enough for any piece.
+    preview_rows: usize,
+    preview_cols: usize,
 }
 impl CliView<io::Stdout> {
-    pub fn new() -> Self {
+    pub fn new(tetris_board: &TetrisBoard) -> Self {
         let mut writer = io::stdout();
         execute!(writer, terminal::EnterAlternateScreen).unwrap();
-        Self { writer }
+        Self {
+            writer,
+            panel_col: Self::panel_col_for(tetris_board),
+            preview_rows: Self::PREVIEW_ROWS,
+            preview_cols: Self::PREVIEW_COLS,
+        }
     }
 }
 impl<W: Write> Drop for CliView<W> {
@@ -22,18 +38,68 @@ impl<W: Write> Drop for CliView<W> {
     }
 }
 impl<W: Write> CliView<W> {
+    const PANEL_MARGIN: u16 = 8;
+    const HOLD_ROW: u16 = 8;
+    const NEXT_ROW: u16 = 8;
+    // Offset of the next panel from the panel column, clear of the score text.
+    const NEXT_COL_OFFSET: u16 = 24;
+    const PREVIEW_ROWS: usize = 2;
+    const PREVIEW_COLS: usize = 4;
+    // Rows below the top of the score panel, which sits under the hold box.
+    // The banner goes below the score, clear of the next pieces, and the
+    // timer for modes that race against the clock under the banner.
+    const EVENT_OFFSET: u16 = 6;
+    const EVENT_WIDTH: usize = 16;
+    const TIMER_OFFSET: u16 = 8;
+
+    fn panel_col_for(tetris_board: &TetrisBoard) -> u16 {
+        // The board is drawn with a wall either side of its columns.
+        tetris_board.num_cols() as u16 + 2 + Self::PANEL_MARGIN
+    }
+    // The score panel starts a row clear of the hold box, so that tall
+    // pieces push it down rather than being drawn over.
+    fn score_row(&self) -> u16 {
+        Self::HOLD_ROW + 1 + self.preview_rows as u16 + 2
+    }
     fn generate_board_string_view(tetris_board: &TetrisBoard) -> Vec<String> {
-        let mut view_lines: Vec<String> = Vec::with_capacity(tetris_board.board.len());
-        for line in &tetris_board.board {
-            let mut line_chars: Vec<u8> = vec!['|' as u8];
-            line_chars.extend(line.iter().map(|x| match x {
-                true => 'o' as u8,
-                false => ' ' as u8,
-            }));
-            line_chars.push('|' as u8);
+        let mut view_lines: Vec<String> = Vec::with_capacity(tetris_board.num_rows());
+        for row in 0..tetris_board.num_rows() {
+            let mut line_chars: Vec<u8> = vec![b'|'];
+            line_chars.extend(
+                (0..tetris_board.num_cols())
+                    .map(|col| Self::cell_char(&tetris_board.cell(row, col))),
+            );
+            line_chars.push(b'|');
             view_lines.push(String::from_utf8(line_chars).expect("Error converting to string."));
         }
-        return view_lines;
+        view_lines
+    }
+    fn cell_char(cell: &Cell) -> u8 {
+        match cell {
+            Cell::Empty => b' ',
+            Cell::Piece(_) | Cell::Garbage => b'o',
+            Cell::Special => b'*',
+        }
+    }
+    /// The colour of a filled cell, in the colour of the piece that filled
+    /// it.
+    fn cell_colour(cell: &Cell) -> style::Color {
+        match cell {
+            Cell::Empty => style::Color::Reset,
+            Cell::Piece(piece_shape) => match piece_shape.colour() {
+                PieceColour::Red => style::Color::Red,
+                PieceColour::Orange => style::Color::DarkYellow,
+                PieceColour::Yellow => style::Color::Yellow,
+                PieceColour::Green => style::Color::Green,
+                PieceColour::Cyan => style::Color::Cyan,
+                PieceColour::Blue => style::Color::Blue,
+                PieceColour::Magenta => style::Color::Magenta,
+                PieceColour::Grey => style::Color::Grey,
+                PieceColour::White => style::Color::White,
+            },
+            Cell::Garbage => style::Color::DarkGrey,
+            Cell::Special => style::Color::White,
+        }
     }
     fn draw_board(&mut self, board_string: Vec<String>) -> std::io::Result<()> {
         queue!(self.writer, cursor::MoveTo(0, 0),)?;
@@ -49,71 +115,252 @@ impl<W: Write> CliView<W> {
 
         self.writer.flush()?;
 
-        return Ok(());
+        Ok(())
+    }
+    /// Paints over the filled cells of the board in the colour of whatever
+    /// filled them.
+    fn draw_stack(&mut self, tetris_board: &TetrisBoard) -> std::io::Result<()> {
+        let bottom_row = tetris_board.num_rows() - 1;
+        for row in 0..tetris_board.num_rows() {
+            for col in 0..tetris_board.num_cols() {
+                let cell = tetris_board.cell(row, col);
+                if cell == Cell::Empty {
+                    continue;
+                }
+                let mut content = (Self::cell_char(&cell) as char).with(Self::cell_colour(&cell));
+                if row == bottom_row {
+                    content = content.underlined();
+                }
+                queue!(
+                    self.writer,
+                    cursor::MoveTo(col as u16 + 1, row as u16),
+                    style::PrintStyledContent(content),
+                )?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
     }
     fn draw_piece(&mut self, piece_coordinates: Vec<Coord>) -> std::io::Result<()> {
-        for coord in piece_coordinates {
+        self.draw_cells(piece_coordinates, "x")
+    }
+    fn draw_ghost(&mut self, ghost_coordinates: Vec<Coord>) -> std::io::Result<()> {
+        self.draw_cells(ghost_coordinates, ".")
+    }
+    fn draw_cells(&mut self, coordinates: Vec<Coord>, cell: &str) -> std::io::Result<()> {
+        // Cells up in the hidden buffer are not drawn.
+        for coord in coordinates.into_iter().filter(|coord| coord.row >= 0) {
             queue!(
                 self.writer,
                 cursor::MoveTo((coord.col + 1) as u16, coord.row as u16),
-                style::Print("x")
+                style::Print(cell)
             )?;
         }
         self.writer.flush()?;
-        return Ok(());
+        Ok(())
+    }
+    /// Grows the hold and next boxes to fit the largest piece in the set.
+    /// They are never smaller than fits the tetrominoes.
+    pub fn fit_previews(&mut self, piece_set: &PieceSet) {
+        for piece_shape in piece_set.pieces() {
+            let shape = piece_shape.shape();
+            let span = |values: Vec<i16>| {
+                (values.iter().max().unwrap() - values.iter().min().unwrap() + 1) as usize
+            };
+            let rows = span(shape.iter().map(|coord| coord.row).collect());
+            let cols = span(shape.iter().map(|coord| coord.col).collect());
+            self.preview_rows = self.preview_rows.max(rows);
+            self.preview_cols = self.preview_cols.max(cols);
+        }
+    }
+    /// Lays out a piece in its spawn orientation in a box of the given size,
+    /// or an empty box when there is no piece.
+    fn generate_piece_preview(
+        piece_shape: Option<PieceShape>,
+        preview_rows: usize,
+        preview_cols: usize,
+    ) -> Vec<String> {
+        let mut preview = vec![vec![b' '; preview_cols]; preview_rows];
+        if let Some(piece_shape) = piece_shape {
+            let shape = piece_shape.shape();
+            let min_col = shape.iter().map(|coord| coord.col).min().unwrap_or(0);
+            let min_row = shape.iter().map(|coord| coord.row).min().unwrap_or(0);
+            for coord in shape {
+                preview[(coord.row - min_row) as usize][(coord.col - min_col) as usize] = b'x';
+            }
+        }
+        preview
+            .into_iter()
+            .map(|line| String::from_utf8(line).expect("Error converting to string."))
+            .collect()
     }
+    pub fn draw_hold(&mut self, held: Option<PieceShape>) -> std::io::Result<()> {
+        queue!(
+            self.writer,
+            cursor::MoveTo(self.panel_col, Self::HOLD_ROW),
+            style::Print("Hold:"),
+        )?;
+        for (i, line) in Self::generate_piece_preview(held, self.preview_rows, self.preview_cols)
+            .iter()
+            .enumerate()
+        {
+            queue!(
+                self.writer,
+                cursor::MoveTo(self.panel_col, Self::HOLD_ROW + 1 + i as u16),
+                style::Print(line),
+            )?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+    /// Draws the upcoming pieces in a column, the next to be played at the top.
+    pub fn draw_next(&mut self, preview: impl Iterator<Item = PieceShape>) -> std::io::Result<()> {
+        let next_col = self.panel_col + Self::NEXT_COL_OFFSET;
+        queue!(
+            self.writer,
+            cursor::MoveTo(next_col, Self::NEXT_ROW),
+            style::Print("Next:"),
+        )?;
+        let mut row = Self::NEXT_ROW + 1;
+        for piece_shape in preview {
+            for line in Self::generate_piece_preview(
+                Some(piece_shape),
+                self.preview_rows,
+                self.preview_cols,
+            ) {
+                queue!(
+                    self.writer,
+                    cursor::MoveTo(next_col, row),
+                    style::Print(line)
+                )?;
+                row += 1;
+            }
+            row += 1;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+    /// Draws the board with the piece in play and its ghost, or just the
+    /// board between pieces.
     pub fn draw_piece_and_board(
         &mut self,
-        piece: &TetrisPiece,
+        piece: Option<&TetrisPiece>,
         board: &TetrisBoard,
     ) -> std::io::Result<()> {
         let board_string = Self::generate_board_string_view(board);
         Self::draw_board(self, board_string)?;
-        Self::draw_piece(self, piece.coordinates())?;
+        Self::draw_stack(self, board)?;
+        if let Some(piece) = piece {
+            Self::draw_ghost(self, piece.landing_coordinates(board))?;
+            Self::draw_piece(self, piece.coordinates())?;
+        }
         Ok(())
     }
+    /// Shows the score and speed, and the time left for modes with a time
+    /// limit.
     pub fn draw_score(
         &mut self,
         score: u64,
         level: u64,
         time_per_turn: u64,
+        clear_streak: &ClearStreak,
+        time_left: Option<Duration>,
     ) -> std::io::Result<()> {
+        let score_row = self.score_row();
+        let back_to_back = match clear_streak.back_to_back() {
+            true => "yes",
+            false => "no",
+        };
         queue!(
             self.writer,
-            cursor::MoveTo(20, 13),
+            cursor::MoveTo(self.panel_col, score_row),
             style::Print(format!("Score: {}", score)),
-            cursor::MoveTo(20, 14),
+            cursor::MoveTo(self.panel_col, score_row + 1),
             style::Print(format!("Current Level: {}", level)),
-            cursor::MoveTo(20, 15),
+            cursor::MoveTo(self.panel_col, score_row + 2),
             style::Print(format!("Time per turn: {} ms", time_per_turn)),
+            // Padded so that a shorter value covers up a longer one.
+            cursor::MoveTo(self.panel_col, score_row + 3),
+            style::Print(format!("Combo: {:<4}", clear_streak.combo())),
+            cursor::MoveTo(self.panel_col, score_row + 4),
+            style::Print(format!("Back-to-back: {:<3}", back_to_back)),
         )?;
-        self.writer.flush()?;
-        return Ok(());
-    }
-    pub fn draw_intro(&mut self) -> std::io::Result<()> {
-        let tetris_art = vec![
-            String::from("##### ##### ##### ###   #####   ### "),
-            String::from("  #   #       #   #  #    #    # "),
-            String::from("  #   ####    #   ##      #     ##"),
-            String::from("  #   #       #   # #     #       #"),
-            String::from("  #   ####    #   #  #  #####   ##"),
-        ];
-        for i in 0..tetris_art.len() {
+        if let Some(time_left) = time_left {
             queue!(
                 self.writer,
-                cursor::MoveTo(20, i as u16),
-                style::Print(&tetris_art[i]),
+                // Padded so that a shorter value covers up a longer one.
+                cursor::MoveTo(self.panel_col, score_row + 5),
+                style::Print(format!("Time left: {:<9}", format_clock(time_left))),
             )?;
         }
+        self.writer.flush()?;
+        Ok(())
+    }
+    /// Shows the time a sprint has been running and the lines it still
+    /// needs.
+    pub fn draw_timer(&mut self, elapsed: Duration, lines_left: u64) -> std::io::Result<()> {
+        let timer_row = self.score_row() + Self::TIMER_OFFSET;
         queue!(
             self.writer,
-            cursor::MoveTo(20, 6),
-            style::Print(
-                "Use the arrows to move, 'x' to rotate clockwise and 'z' to rotate anticlockise. Hit Esc to quit."
-            ),
+            cursor::MoveTo(self.panel_col, timer_row),
+            style::Print(format!("Time: {}", format_clock(elapsed))),
+            // Padded so that a shorter value covers up a longer one.
+            cursor::MoveTo(self.panel_col, timer_row + 1),
+            style::Print(format!("Lines left: {:<3}", lines_left)),
         )?;
         self.writer.flush()?;
-        return Ok(());
+        Ok(())
+    }
+    /// Shows a banner for events worth announcing until it is replaced by
+    /// the next one, or clears the banner for any other event or none.
+    pub fn draw_event(&mut self, event: Option<GameEvent>) -> std::io::Result<()> {
+        let banner = match event {
+            Some(GameEvent::PerfectClear) => "PERFECT CLEAR",
+            _other => "",
+        };
+        let event_row = self.score_row() + Self::EVENT_OFFSET;
+        queue!(
+            self.writer,
+            cursor::MoveTo(self.panel_col, event_row),
+            style::Print(format!("{:<width$}", banner, width = Self::EVENT_WIDTH)),
+        )?;
+        self.writer.flush()?;
+        Ok(())
+    }
+    pub fn draw_intro(&mut self, key_bindings: &KeyBindings) -> std::io::Result<()> {
+        let tetris_art = [
+            "##### ##### ##### ###   #####   ### ",
+            "  #   #       #   #  #    #    # ",
+            "  #   ####    #   ##      #     ##",
+            "  #   #       #   # #     #       #",
+            "  #   ####    #   #  #  #####   ##",
+        ];
+        for (i, line) in tetris_art.iter().enumerate() {
+            queue!(
+                self.writer,
+                cursor::MoveTo(self.panel_col, i as u16),
+                style::Print(line),
+            )?;
+        }
+        let instructions = [
+            format!(
+                "Use the arrows to move, '{}' to rotate clockwise and '{}' to rotate anticlockise.",
+                key_bindings.rotate_clockwise, key_bindings.rotate_anticlockwise
+            ),
+            format!(
+                "'{}' to rotate 180, space to hard drop, '{}' to hold. Hit Esc to quit.",
+                key_bindings.rotate_180, key_bindings.hold
+            ),
+        ];
+        for (i, line) in instructions.iter().enumerate() {
+            queue!(
+                self.writer,
+                cursor::MoveTo(self.panel_col, 6 + i as u16),
+                style::Print(line),
+            )?;
+        }
+        self.writer.flush()?;
+        Ok(())
     }
 }
 
@@ -132,6 +379,14 @@ mod tests {
             Ok(())
         }
     }
+    fn test_view() -> CliView<TestWriter> {
+        CliView {
+            writer: TestWriter { buffer: Vec::new() },
+            panel_col: 20,
+            preview_rows: 2,
+            preview_cols: 4,
+        }
+    }
     #[test]
     fn test_cli_view_generates_board() {
         let expected_string = vec![String::from("|          |"); 16];
@@ -140,21 +395,304 @@ mod tests {
         assert_eq!(cli_string, expected_string);
     }
 
+    #[test]
+    fn test_cli_view_generates_board_of_any_size() {
+        let expected_string = vec![String::from("|    |"); 20];
+        let tetris_board = TetrisBoard::with_size(20, 4);
+        let cli_string = CliView::<TestWriter>::generate_board_string_view(&tetris_board);
+        assert_eq!(cli_string, expected_string);
+    }
+
+    #[test]
+    fn test_cli_view_generates_piece_preview() {
+        assert_eq!(
+            CliView::<TestWriter>::generate_piece_preview(Some(PieceShape::T), 2, 4),
+            vec![String::from(" x  "), String::from("xxx ")]
+        );
+        assert_eq!(
+            CliView::<TestWriter>::generate_piece_preview(Some(PieceShape::Bar), 2, 4),
+            vec![String::from("xxxx"), String::from("    ")]
+        );
+        assert_eq!(
+            CliView::<TestWriter>::generate_piece_preview(None, 2, 4),
+            vec![String::from("    "); 2]
+        );
+    }
+
+    #[test]
+    fn test_cli_view_fits_previews_to_piece_set() {
+        let mut test_viewer = test_view();
+        test_viewer.fit_previews(&PieceSet::standard());
+        assert_eq!((test_viewer.preview_rows, test_viewer.preview_cols), (2, 4));
+        let piece_set = PieceSet::parse(
+            "[I5]\ncells = -2,0 -1,0 0,0 1,0 2,0\n[X]\ncells = 0,-1 -1,0 0,0 1,0 0,1",
+        )
+        .unwrap();
+        test_viewer.fit_previews(&piece_set);
+        assert_eq!((test_viewer.preview_rows, test_viewer.preview_cols), (3, 5));
+        assert_eq!(
+            CliView::<TestWriter>::generate_piece_preview(
+                Some(piece_set.pieces()[1].clone()),
+                3,
+                5
+            ),
+            vec![
+                String::from(" x   "),
+                String::from("xxx  "),
+                String::from(" x   ")
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_next_pieces() {
+        let next_col = 20 + CliView::<TestWriter>::NEXT_COL_OFFSET;
+        let expected_buffer: Vec<u8> = [
+            (next_col, 8, "Next:"),
+            (next_col, 9, "xxxx"),
+            (next_col, 10, "    "),
+            (next_col, 12, "xx  "),
+            (next_col, 13, "xx  "),
+        ]
+        .into_iter()
+        .flat_map(|(col, row, text)| {
+            CommandMapping::move_to_position(col, row)
+                .into_iter()
+                .chain(text.bytes())
+        })
+        .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_next([PieceShape::Bar, PieceShape::Square].into_iter())
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_ghost() {
+        let expected_buffer: Vec<u8> = CommandMapping::move_to(2, 5)
+            .into_iter()
+            .chain([b'.'])
+            .chain(CommandMapping::move_to(3, 5))
+            .chain([b'.'])
+            .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_ghost(vec![Coord { col: 1, row: 5 }, Coord { col: 2, row: 5 }])
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_score_panel() {
+        let expected_buffer: Vec<u8> = [
+            (13, "Score: 120"),
+            (14, "Current Level: 1"),
+            (15, "Time per turn: 716 ms"),
+            (16, "Combo: 0   "),
+            (17, "Back-to-back: no "),
+        ]
+        .into_iter()
+        .flat_map(|(row, text)| {
+            CommandMapping::move_to_position(20, row)
+                .into_iter()
+                .chain(text.bytes())
+        })
+        .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_score(120, 1, 716, &ClearStreak::new(), None)
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_countdown_under_score() {
+        let countdown: Vec<u8> = CommandMapping::move_to_position(20, 18)
+            .into_iter()
+            .chain("Time left: 1:59.983 ".bytes())
+            .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_score(
+                0,
+                0,
+                800,
+                &ClearStreak::new(),
+                Some(Duration::from_millis(119_983)),
+            )
+            .expect("Writing to test writer failed.");
+        assert!(test_viewer.writer.buffer.ends_with(&countdown));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_countdown_covers_longer_times() {
+        let countdown: Vec<u8> = CommandMapping::move_to_position(20, 18)
+            .into_iter()
+            .chain("Time left: 9:59.983 ".bytes())
+            .collect();
+
+        let mut test_viewer = test_view();
+        for millis in [600_000, 599_983] {
+            test_viewer
+                .draw_score(
+                    0,
+                    0,
+                    800,
+                    &ClearStreak::new(),
+                    Some(Duration::from_millis(millis)),
+                )
+                .expect("Writing to test writer failed.");
+        }
+        assert!(test_viewer.writer.buffer.ends_with(&countdown));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_moves_score_panel_below_tall_pieces() {
+        let mut test_viewer = test_view();
+        let piece_set = PieceSet::parse("[I5]\ncells = 0,-2 0,-1 0,0 0,1 0,2").unwrap();
+        test_viewer.fit_previews(&piece_set);
+        assert_eq!(test_viewer.preview_rows, 5);
+        test_viewer
+            .draw_hold(Some(piece_set.pieces()[0].clone()))
+            .expect("Writing to test writer failed.");
+        // The hold box takes rows 9 to 13, so the score starts at 16.
+        let hold_bottom: Vec<u8> = CommandMapping::move_to_position(20, 13)
+            .into_iter()
+            .chain("x   ".bytes())
+            .collect();
+        assert!(test_viewer.writer.buffer.ends_with(&hold_bottom));
+
+        test_viewer.writer.buffer.clear();
+        test_viewer
+            .draw_score(0, 0, 800, &ClearStreak::new(), None)
+            .expect("Writing to test writer failed.");
+        let score: Vec<u8> = CommandMapping::move_to_position(20, 16)
+            .into_iter()
+            .chain("Score: 0".bytes())
+            .collect();
+        assert!(test_viewer.writer.buffer.starts_with(&score));
+
+        test_viewer.writer.buffer.clear();
+        test_viewer
+            .draw_event(None)
+            .expect("Writing to test writer failed.");
+        assert!(test_viewer
+            .writer
+            .buffer
+            .starts_with(&CommandMapping::move_to_position(20, 22)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_and_clears_event_banner() {
+        let expected_buffer: Vec<u8> = [(19, "PERFECT CLEAR   "), (19, "                ")]
+            .into_iter()
+            .flat_map(|(row, text)| {
+                CommandMapping::move_to_position(20, row)
+                    .into_iter()
+                    .chain(text.bytes())
+            })
+            .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_event(Some(GameEvent::PerfectClear))
+            .expect("Writing to test writer failed.");
+        test_viewer
+            .draw_event(None)
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_writes_timer() {
+        let expected_buffer: Vec<u8> = [(21, "Time: 0:41.016"), (22, "Lines left: 7  ")]
+            .into_iter()
+            .flat_map(|(row, text)| {
+                CommandMapping::move_to_position(20, row)
+                    .into_iter()
+                    .chain(text.bytes())
+            })
+            .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_timer(Duration::from_millis(41_016), 7)
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[test]
+    fn test_cli_view_generates_board_by_cell() {
+        let mut tetris_board = TetrisBoard::with_size(4, 4);
+        tetris_board.set_cell(3, 0, Cell::Piece(PieceShape::T));
+        tetris_board.set_cell(3, 1, Cell::Garbage);
+        tetris_board.set_cell(3, 2, Cell::Special);
+        assert_eq!(
+            CliView::<TestWriter>::generate_board_string_view(&tetris_board)[3],
+            "|oo* |"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cli_view_colours_stack_by_cell() {
+        let mut tetris_board = TetrisBoard::with_size(4, 4);
+        tetris_board.set_cell(2, 1, Cell::Piece(PieceShape::T));
+        let expected_buffer: Vec<u8> = CommandMapping::move_to(2, 2)
+            .into_iter()
+            .chain(b"\x1b[38;5;13mo\x1b[39m".iter().copied())
+            .collect();
+
+        let mut test_viewer = test_view();
+        test_viewer
+            .draw_stack(&tetris_board)
+            .expect("Writing to test writer failed.");
+        assert_eq!(test_viewer.writer.buffer, expected_buffer);
+    }
+
+    #[test]
+    fn test_cli_view_panel_sits_beside_board() {
+        assert_eq!(
+            CliView::<TestWriter>::panel_col_for(&TetrisBoard::new()),
+            20
+        );
+        assert_eq!(
+            CliView::<TestWriter>::panel_col_for(&TetrisBoard::with_size(20, 30)),
+            40
+        );
+    }
+
     struct CommandMapping {}
     impl CommandMapping {
         const MOVE_TO_START: [u8; 6] = [27, 91, 49, 59, 49, 72];
         const MOVE_TO_NEXT_LINE: [u8; 4] = [27, 91, 49, 69];
         const SET_UNDERLINED: [u8; 4] = [27, 91, 52, 109];
         const SET_NOT_UNDERLINED: [u8; 5] = [27, 91, 50, 52, 109];
+        fn move_to_position(col: u16, row: u16) -> Vec<u8> {
+            format!("\x1b[{};{}H", row + 1, col + 1).into_bytes()
+        }
         fn move_to(col: u8, row: u8) -> [u8; 6] {
-            return [
+            [
                 Self::MOVE_TO_START[0],
                 Self::MOVE_TO_START[1],
                 Self::MOVE_TO_START[2] + row,
                 Self::MOVE_TO_START[3],
                 Self::MOVE_TO_START[4] + col,
                 Self::MOVE_TO_START[5],
-            ];
+            ]
         }
     }
 
@@ -175,9 +713,7 @@ mod tests {
             .collect();
 
         let cli_string = vec![String::from(board_row); 2];
-        let mut test_viewer = CliView {
-            writer: TestWriter { buffer: Vec::new() },
-        };
+        let mut test_viewer = test_view();
         test_viewer
             .draw_board(cli_string)
             .expect("Writing to test writer failed.");
@@ -190,18 +726,16 @@ mod tests {
         // Construct expected buffer from commands
         let expected_buffer: Vec<u8> = CommandMapping::move_to(2, 1)
             .into_iter()
-            .chain(['x' as u8])
+            .chain([b'x'])
             .chain(CommandMapping::move_to(3, 1))
-            .chain(['x' as u8])
+            .chain([b'x'])
             .chain(CommandMapping::move_to(3, 2))
-            .chain(['x' as u8])
+            .chain([b'x'])
             .chain(CommandMapping::move_to(4, 2))
-            .chain(['x' as u8])
+            .chain([b'x'])
             .collect();
 
-        let mut test_viewer = CliView {
-            writer: TestWriter { buffer: Vec::new() },
-        };
+        let mut test_viewer = test_view();
         let piece_coords = vec![
             Coord { col: 1, row: 1 },
             Coord { col: 2, row: 1 },